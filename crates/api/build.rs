@@ -15,7 +15,6 @@ use vergen::{vergen, Config, ShaKind};
 #[warn(clippy::style)]
 #[warn(clippy::complexity)]
 #[warn(clippy::perf)]
-
 /// Strongly-typed manifest
 #[derive(Deserialize)]
 pub struct Manifest {
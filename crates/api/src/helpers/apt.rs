@@ -0,0 +1,389 @@
+use crate::{
+	types::{Package, Repository},
+	utility::{relative_filename, ARCHITECTURE_ALL},
+};
+use anyhow::Result;
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression as GzLevel};
+use sha2::{Digest, Sha256};
+use std::{fmt::Write as _, io::Write};
+
+/// Suite and component that every synthesized repository is served under
+pub const APT_SUITE: &str = "stable";
+pub const APT_COMPONENT: &str = "main";
+
+/// Indices are rebuilt whenever a repository is indexed, so speed matters more than size
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression formats that `Packages` indices are served in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+	None,
+	Gzip,
+	Zstd,
+}
+
+impl Compression {
+	pub const ALL: [Compression; 3] = [Compression::None, Compression::Gzip, Compression::Zstd];
+
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Compression::None => "",
+			Compression::Gzip => ".gz",
+			Compression::Zstd => ".zst",
+		}
+	}
+
+	pub fn content_type(&self) -> &'static str {
+		match self {
+			Compression::None => "text/plain; charset=utf-8",
+			Compression::Gzip => "application/gzip",
+			Compression::Zstd => "application/zstd",
+		}
+	}
+
+	pub fn from_file_name(file_name: &str) -> Option<Self> {
+		Compression::ALL
+			.into_iter()
+			.find(|compression| file_name == format!("Packages{}", compression.extension()))
+	}
+
+	pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+		match self {
+			Compression::None => Ok(data.to_vec()),
+			Compression::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+				encoder.write_all(data)?;
+				Ok(encoder.finish()?)
+			}
+			Compression::Zstd => Ok(zstd::encode_all(data, ZSTD_LEVEL)?),
+		}
+	}
+}
+
+/// A single paragraph of a Debian control file
+#[derive(Default)]
+struct Stanza {
+	body: String,
+}
+
+impl Stanza {
+	/// Appends a field, skipping empty values and folding multiline ones
+	fn field(&mut self, name: &str, value: Option<String>) -> &mut Self {
+		let value = match value {
+			Some(value) if !value.trim().is_empty() => value,
+			_ => return self,
+		};
+
+		let mut lines = value.trim().lines();
+		let _ = write!(
+			self.body,
+			"{}: {}",
+			name,
+			lines.next().unwrap_or_default().trim()
+		);
+		for line in lines {
+			match line.trim().is_empty() {
+				true => self.body.push_str("\n ."),
+				false => {
+					let _ = write!(self.body, "\n {}", line.trim());
+				}
+			}
+		}
+
+		self.body.push('\n');
+		self
+	}
+}
+
+/// Reconstructs the control stanza of an indexed package
+/// The filename is relative to the repository, which redirects it upstream
+pub fn package_stanza(package: &Package) -> String {
+	let mut stanza = Stanza::default();
+	stanza
+		.field("Package", Some(package.package_id.clone()))
+		.field("Version", Some(package.version.clone()))
+		.field("Architecture", Some(package.architecture.clone()))
+		.field("Name", package.name.clone())
+		.field("Description", package.description.clone())
+		.field("Author", package.author.clone())
+		.field("Maintainer", package.maintainer.clone())
+		.field("Section", package.section.clone())
+		.field("Pre-Depends", package.pre_depends.clone())
+		.field("Depends", package.depends.clone())
+		.field("Conflicts", package.conflicts.clone())
+		.field("Breaks", package.breaks.clone())
+		.field("Provides", package.provides.clone())
+		.field("Replaces", package.replaces.clone())
+		.field("Depiction", package.depiction.clone())
+		.field("Native-Depiction", package.native_depiction.clone())
+		.field("SileoDepiction", package.sileo_depiction.clone())
+		.field("Header", package.header_url.clone())
+		.field("Tint", package.tint_color.clone())
+		.field("Icon", package.icon_url.clone())
+		.field("Tag", package.tags.as_ref().map(|tags| tags.join(", ")))
+		.field(
+			"Installed-Size",
+			package.installed_size.map(|size| size.to_string()),
+		)
+		.field(
+			"Filename",
+			Some(relative_filename(&package.package_filename).to_string()),
+		)
+		.field("Size", Some(package.package_size.to_string()))
+		.field("SHA256", package.sha256_hash.clone());
+
+	stanza.body
+}
+
+/// Architectures that get their own `binary-*` index, `all` packages are
+/// merged into every one of them unless nothing else exists
+pub fn index_architectures(packages: &[Package]) -> Vec<String> {
+	let mut architectures = packages
+		.iter()
+		.map(|package| package.architecture.clone())
+		.collect::<Vec<String>>();
+
+	architectures.sort();
+	architectures.dedup();
+
+	match architectures
+		.iter()
+		.all(|architecture| architecture == ARCHITECTURE_ALL)
+	{
+		true => architectures,
+		false => architectures
+			.into_iter()
+			.filter(|architecture| architecture != ARCHITECTURE_ALL)
+			.collect(),
+	}
+}
+
+/// Builds the `Packages` index of a single architecture
+pub fn packages_index(packages: &[Package], architecture: &str) -> String {
+	packages
+		.iter()
+		.filter(|package| {
+			package.architecture == architecture || package.architecture == ARCHITECTURE_ALL
+		})
+		.map(package_stanza)
+		.collect::<Vec<String>>()
+		.join("\n")
+}
+
+/// Every `Packages` index of a repository, built for one indexing run
+pub struct AptIndices {
+	/// `origin_last_updated` of the repository when the indices were built
+	pub last_updated: String,
+	pub architectures: Vec<String>,
+	/// Contents of every index, keyed by its path relative to the suite directory
+	pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl AptIndices {
+	/// Builds and compresses the index of every architecture
+	/// Compression is CPU bound, so this should be called off the runtime
+	pub fn build(packages: &[Package], repository: &Repository) -> Result<Self> {
+		let architectures = index_architectures(packages);
+		let mut files = Vec::new();
+		for architecture in &architectures {
+			let index = packages_index(packages, architecture);
+			for compression in Compression::ALL {
+				files.push((
+					index_path(architecture, compression),
+					compression.compress(index.as_bytes())?,
+				));
+			}
+		}
+
+		Ok(AptIndices {
+			last_updated: repository.origin_last_updated.clone(),
+			architectures,
+			files,
+		})
+	}
+
+	pub fn file(&self, path: &str) -> Option<&[u8]> {
+		self.files
+			.iter()
+			.find(|(file, _)| file == path)
+			.map(|(_, contents)| contents.as_slice())
+	}
+}
+
+/// Path of an index file relative to the suite directory
+pub fn index_path(architecture: &str, compression: Compression) -> String {
+	format!(
+		"{}/binary-{}/Packages{}",
+		APT_COMPONENT,
+		architecture,
+		compression.extension()
+	)
+}
+
+/// Builds the `Release` file of a repository, listing the SHA256 of every index
+pub fn release_file(
+	repository: &Repository,
+	architectures: &[String],
+	indices: &[(String, Vec<u8>)],
+) -> String {
+	let flag = |value: bool| match value {
+		true => Some("yes".to_string()),
+		false => Some("no".to_string()),
+	};
+
+	let mut stanza = Stanza::default();
+	stanza
+		.field(
+			"Origin",
+			repository
+				.name
+				.clone()
+				.or_else(|| Some(repository.id.clone())),
+		)
+		.field("Label", repository.name.clone())
+		.field("Suite", Some(APT_SUITE.to_string()))
+		.field("Codename", Some(APT_SUITE.to_string()))
+		.field("Version", repository.version.clone())
+		.field(
+			"Date",
+			Some(Utc::now().format("%a, %d %b %Y %H:%M:%S UTC").to_string()),
+		)
+		.field("Architectures", Some(architectures.join(" ")))
+		.field("Components", Some(APT_COMPONENT.to_string()))
+		.field("Description", repository.description.clone())
+		.field("Canister-Repository-URI", Some(repository.uri.clone()))
+		.field(
+			"Canister-Origin-Hostname",
+			Some(repository.origin_hostname.clone()),
+		)
+		.field(
+			"Canister-Origin-Release-Path",
+			Some(repository.origin_release_path.clone()),
+		)
+		.field(
+			"Canister-Origin-Release-Hash",
+			Some(repository.origin_release_hash.clone()),
+		)
+		.field(
+			"Canister-Origin-Packages-Path",
+			Some(repository.origin_packages_path.clone()),
+		)
+		.field(
+			"Canister-Origin-Packages-Hash",
+			Some(repository.origin_packages_hash.clone()),
+		)
+		.field(
+			"Canister-Origin-Last-Updated",
+			Some(repository.origin_last_updated.clone()),
+		)
+		.field(
+			"Canister-Origin-Has-InRelease",
+			flag(repository.origin_has_in_release),
+		)
+		.field(
+			"Canister-Origin-Has-Release-GPG",
+			flag(repository.origin_has_release_gpg),
+		)
+		.field(
+			"Canister-Origin-Supports-Payment-V1",
+			flag(repository.origin_supports_payment_v1),
+		)
+		.field(
+			"Canister-Origin-Supports-Payment-V2",
+			flag(repository.origin_supports_payment_v2),
+		)
+		.field(
+			"Canister-Origin-Uses-HTTPS",
+			flag(repository.origin_uses_https),
+		);
+
+	let mut body = stanza.body;
+	body.push_str("SHA256:\n");
+	for (path, contents) in indices {
+		let _ = writeln!(
+			body,
+			" {:x} {:>16} {}",
+			Sha256::digest(contents),
+			contents.len(),
+			path
+		);
+	}
+
+	body
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use flate2::read::GzDecoder;
+	use serde_json::{from_value, json};
+	use std::io::Read;
+
+	#[test]
+	fn stanza_fields_are_folded() {
+		let mut stanza = Stanza::default();
+		stanza
+			.field("Package", Some("ws.hbang.common".to_string()))
+			.field("Depiction", Some(" ".to_string()))
+			.field("Author", None)
+			.field(
+				"Description",
+				Some("Support library\nUsed by tweaks\n\nMore".to_string()),
+			);
+
+		assert_eq!(
+			stanza.body,
+			"Package: ws.hbang.common\nDescription: Support library\n Used by tweaks\n .\n More\n"
+		);
+	}
+
+	#[test]
+	fn package_stanzas_use_relative_filenames() {
+		let package: Package = from_value(json!({
+			"id": "c3",
+			"package_id": "ws.hbang.common",
+			"latest_version": true,
+			"visible": true,
+			"quality": 1,
+			"repository_id": "chariz",
+			"price": "Free",
+			"version": "2.0",
+			"architecture": "iphoneos-arm64",
+			"package_filename": "./debs/common_2.0.deb",
+			"package_size": 200
+		}))
+		.unwrap();
+
+		assert_eq!(
+			package_stanza(&package),
+			"Package: ws.hbang.common\nVersion: 2.0\nArchitecture: iphoneos-arm64\n\
+			 Filename: debs/common_2.0.deb\nSize: 200\n"
+		);
+	}
+
+	#[test]
+	fn compression_round_trips() {
+		let data = b"Package: ws.hbang.common\nVersion: 2.0\n";
+
+		let mut decoded = Vec::new();
+		let gzip = Compression::Gzip.compress(data).unwrap();
+		GzDecoder::new(gzip.as_slice())
+			.read_to_end(&mut decoded)
+			.unwrap();
+		assert_eq!(decoded, data);
+
+		let zstd = Compression::Zstd.compress(data).unwrap();
+		assert_eq!(zstd::decode_all(zstd.as_slice()).unwrap(), data);
+
+		assert_eq!(
+			Compression::from_file_name("Packages.zst"),
+			Some(Compression::Zstd)
+		);
+		assert_eq!(Compression::from_file_name("Packages.xz"), None);
+		assert_eq!(
+			index_path("iphoneos-arm64", Compression::Gzip),
+			"main/binary-iphoneos-arm64/Packages.gz"
+		);
+	}
+}
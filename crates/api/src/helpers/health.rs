@@ -0,0 +1,277 @@
+use crate::{
+	helpers::{
+		ingest::{sink_names, SinkConfig},
+		pg_client,
+		search::SearchEngine,
+	},
+	AppState,
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+	collections::BTreeMap,
+	future::Future,
+	path::Path,
+	time::{Duration, Instant},
+};
+use tokio::{fs, task::JoinSet, time::timeout};
+
+/// Longest a single dependency may take before it's reported as unhealthy
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a readiness report is reused, so frequent probes from every
+/// kubelet don't each reach the dependencies
+pub const READINESS_TTL: Duration = Duration::from_secs(5);
+
+/// Outcome of probing a single dependency
+#[derive(Clone, Debug, Serialize)]
+pub struct DependencyHealth {
+	pub healthy: bool,
+	/// Whether the API is unusable while this dependency is down
+	pub required: bool,
+	pub latency_ms: f64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+/// Health of every dependency, checked at the same time
+#[derive(Clone, Debug, Serialize)]
+pub struct Readiness {
+	/// Every required dependency is healthy
+	pub ready: bool,
+	pub checked_at: DateTime<Utc>,
+	pub dependencies: Dependencies,
+	pub ingest_queue: QueueHealth,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Dependencies {
+	pub postgres: DependencyHealth,
+	pub typesense: DependencyHealth,
+	pub piracy_list: DependencyHealth,
+	/// Every configured download event sink by name, besides stdout
+	pub sinks: BTreeMap<String, DependencyHealth>,
+}
+
+/// How many download events are spooled, new ones are dropped at capacity
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueHealth {
+	pub healthy: bool,
+	/// Events the furthest behind sink is waiting for
+	pub depth: usize,
+	pub capacity: usize,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+/// Runs a probe with the timeout, measuring how long it took
+async fn probe<F>(required: bool, check: F) -> DependencyHealth
+where
+	F: Future<Output = Result<()>>,
+{
+	let started = Instant::now();
+	let result = match timeout(PROBE_TIMEOUT, check).await {
+		Ok(result) => result,
+		Err(_) => Err(anyhow!("Timed out after {}s", PROBE_TIMEOUT.as_secs())),
+	};
+
+	DependencyHealth {
+		healthy: result.is_ok(),
+		required,
+		latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+		error: result.err().map(|e| e.to_string()),
+	}
+}
+
+async fn check_postgres(state: &AppState) -> Result<()> {
+	let client = pg_client(&state.pool).await?;
+	client.query_one("SELECT 1;", &[]).await?;
+	Ok(())
+}
+
+/// Checks a download event sink the way events are delivered to it
+async fn check_sink(state: &AppState, sink: &SinkConfig) -> Result<()> {
+	let request = match sink {
+		// Vector accepts an empty batch without emitting events
+		SinkConfig::Vector => state.http.post(&state.config.vector_url).json(&json!([])),
+		// ClickHouse answers on /ping without credentials or a database
+		SinkConfig::ClickHouse(url) => {
+			let mut ping = url.clone();
+			ping.set_path("/ping");
+			ping.set_query(None);
+			let _ = ping.set_username("");
+			let _ = ping.set_password(None);
+			state.http.get(ping)
+		}
+		// The REST proxy describes the topic at the URL events are produced to
+		SinkConfig::Kafka(url) => state.http.get(url.clone()),
+		SinkConfig::File(dir) => return check_writable(dir).await,
+		SinkConfig::Stdout => return Ok(()),
+	};
+
+	request.send().await?.error_for_status()?;
+	Ok(())
+}
+
+/// Checks that a file can be created in the directory
+async fn check_writable(dir: &Path) -> Result<()> {
+	let probe = dir.join(".canister-probe");
+	fs::write(&probe, b"").await?;
+	fs::remove_file(&probe).await?;
+	Ok(())
+}
+
+/// Probes every sink but stdout concurrently, keyed by their names
+async fn check_sinks(state: &AppState) -> BTreeMap<String, DependencyHealth> {
+	let sinks = &state.config.ingest_sinks;
+	let names = sink_names(&sinks.iter().map(SinkConfig::name).collect::<Vec<&str>>());
+
+	let mut probes = JoinSet::new();
+	for (name, sink) in names.into_iter().zip(sinks.iter().cloned()) {
+		if sink == SinkConfig::Stdout {
+			continue;
+		}
+
+		let state = state.clone();
+		probes.spawn(async move { (name, probe(false, check_sink(&state, &sink)).await) });
+	}
+
+	let mut health = BTreeMap::new();
+	while let Some(probed) = probes.join_next().await {
+		if let Ok((name, sink)) = probed {
+			health.insert(name, sink);
+		}
+	}
+
+	health
+}
+
+/// Compares the spool depth with the capacity, past which events are dropped
+async fn check_ingest_queue(state: &AppState) -> QueueHealth {
+	let capacity = state.config.ingest_queue_capacity;
+	match state.ingest.depth().await {
+		Ok(depth) => QueueHealth {
+			healthy: depth < capacity,
+			depth,
+			capacity,
+			error: match depth < capacity {
+				true => None,
+				false => Some("Full, new download events are dropped".to_string()),
+			},
+		},
+		Err(e) => QueueHealth {
+			healthy: false,
+			depth: 0,
+			capacity,
+			error: Some(e.to_string()),
+		},
+	}
+}
+
+/// Checks that the piracy list source still serves a list of repositories
+async fn check_piracy_list(state: &AppState) -> Result<()> {
+	let list = state
+		.http
+		.get(&state.config.piracy_url)
+		.send()
+		.await?
+		.error_for_status()?
+		.json::<Value>()
+		.await?;
+
+	match list.is_array() {
+		true => Ok(()),
+		false => Err(anyhow!("Expected a list of repositories")),
+	}
+}
+
+/// Probes every dependency concurrently
+/// Typesense is only required when it serves search by default
+pub async fn check_readiness(state: &AppState) -> Readiness {
+	let (postgres, typesense, piracy_list, sinks, ingest_queue) = tokio::join!(
+		probe(true, check_postgres(state)),
+		probe(
+			state.search_engine == SearchEngine::Typesense,
+			state.typesense.health()
+		),
+		probe(false, check_piracy_list(state)),
+		check_sinks(state),
+		check_ingest_queue(state),
+	);
+
+	let dependencies = Dependencies {
+		postgres,
+		typesense,
+		piracy_list,
+		sinks,
+	};
+
+	let ready = [
+		&dependencies.postgres,
+		&dependencies.typesense,
+		&dependencies.piracy_list,
+	]
+	.into_iter()
+	.chain(dependencies.sinks.values())
+	.all(|dependency| dependency.healthy || !dependency.required);
+
+	Readiness {
+		ready,
+		checked_at: Utc::now(),
+		dependencies,
+		ingest_queue,
+	}
+}
+
+/// Returns the last readiness report while it's fresh, otherwise checks again
+/// Requests arriving during a check wait for it instead of starting their own
+pub async fn cached_readiness(state: &AppState) -> Readiness {
+	let mut cached = state.caches.readiness.lock().await;
+
+	if let Some((checked, readiness)) = cached.as_ref() {
+		if checked.elapsed() < READINESS_TTL {
+			return readiness.clone();
+		}
+	}
+
+	let readiness = check_readiness(state).await;
+	*cached = Some((Instant::now(), readiness.clone()));
+	readiness
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn probes_report_errors_and_timeouts() {
+		let healthy = probe(true, async { Ok(()) }).await;
+		assert!(healthy.healthy);
+		assert!(healthy.error.is_none());
+
+		let failed = probe(false, async { Err(anyhow!("Connection refused")) }).await;
+		assert!(!failed.healthy);
+		assert!(!failed.required);
+		assert_eq!(failed.error.as_deref(), Some("Connection refused"));
+
+		tokio::time::pause();
+		let slow = probe(true, async {
+			tokio::time::sleep(PROBE_TIMEOUT * 2).await;
+			Ok(())
+		})
+		.await;
+
+		assert!(!slow.healthy);
+		assert_eq!(slow.error.as_deref(), Some("Timed out after 2s"));
+	}
+
+	#[tokio::test]
+	async fn file_sinks_need_a_writable_directory() {
+		let dir = tempfile::tempdir().unwrap();
+		assert!(check_writable(dir.path()).await.is_ok());
+		assert!(check_writable(&dir.path().join("missing")).await.is_err());
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+	}
+}
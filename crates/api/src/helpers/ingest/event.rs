@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// A package download reported by a client, enriched with its client hints
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadEvent {
+	pub package_id: String,
+	pub package_version: String,
+	pub package_author: String,
+	pub package_maintainer: String,
+	pub repository_uri: String,
+	pub repository_suite: String,
+	pub repository_component: String,
+
+	pub client: String,
+	pub client_version: String,
+	pub jailbreak: String,
+	pub jailbreak_version: String,
+	pub distribution: String,
+	pub distribution_version: String,
+
+	pub client_architecture: String,
+	pub client_bitness: String,
+	pub device: String,
+	pub device_platform: String,
+	pub device_version: String,
+
+	/// ID of the package in the database, when it could be resolved
+	pub database_uuid: Option<String>,
+	/// Unix timestamp of when the event was received
+	pub time: i64,
+}
+
+#[cfg(test)]
+impl DownloadEvent {
+	/// Event for the given package with every other field filled in
+	pub fn example(package_id: &str) -> Self {
+		DownloadEvent {
+			package_id: package_id.to_string(),
+			package_version: "1.0".to_string(),
+			package_author: "none".to_string(),
+			package_maintainer: "none".to_string(),
+			repository_uri: "https://repo.chariz.com".to_string(),
+			repository_suite: "./".to_string(),
+			repository_component: "none".to_string(),
+
+			client: "Sileo".to_string(),
+			client_version: "2.5".to_string(),
+			jailbreak: "Dopamine".to_string(),
+			jailbreak_version: "2.0".to_string(),
+			distribution: "unknown".to_string(),
+			distribution_version: "unknown".to_string(),
+
+			client_architecture: "arm64".to_string(),
+			client_bitness: "64".to_string(),
+			device: "iPhone14,2".to_string(),
+			device_platform: "iOS".to_string(),
+			device_version: "16.5".to_string(),
+
+			database_uuid: None,
+			time: 1_700_000_000,
+		}
+	}
+}
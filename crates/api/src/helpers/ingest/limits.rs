@@ -0,0 +1,177 @@
+use crate::utility::RuntimeConfig;
+use std::{
+	collections::HashMap,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// How often entries that no longer matter are swept out of memory
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+struct Buckets {
+	buckets: HashMap<String, Bucket>,
+	swept: Instant,
+}
+
+/// Token bucket per key, allowing bursts of up to a minute's worth of events
+/// Buckets are refilled continuously and forgotten once they're full again
+pub struct RateLimiter {
+	per_minute: f64,
+	state: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+	pub fn new(per_minute: usize) -> Self {
+		RateLimiter {
+			per_minute: per_minute as f64,
+			state: Mutex::new(Buckets {
+				buckets: HashMap::new(),
+				swept: Instant::now(),
+			}),
+		}
+	}
+
+	fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+		let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+		(bucket.tokens + elapsed * self.per_minute / 60.0).min(self.per_minute)
+	}
+
+	/// Takes `cost` tokens from the bucket of `key`, or returns how long to
+	/// wait until enough of them are available
+	pub fn acquire(&self, key: &str, cost: usize, now: Instant) -> Result<(), Duration> {
+		let mut state = self.state.lock().unwrap();
+
+		if now.duration_since(state.swept) >= SWEEP_INTERVAL {
+			let mut buckets = std::mem::take(&mut state.buckets);
+			buckets.retain(|_, bucket| self.refill(bucket, now) < self.per_minute);
+			state.buckets = buckets;
+			state.swept = now;
+		}
+
+		let bucket = state.buckets.entry(key.to_string()).or_insert(Bucket {
+			tokens: self.per_minute,
+			updated: now,
+		});
+
+		bucket.tokens = self.refill(bucket, now);
+		bucket.updated = now;
+
+		let cost = cost as f64;
+		if bucket.tokens >= cost {
+			bucket.tokens -= cost;
+			return Ok(());
+		}
+
+		let missing = cost - bucket.tokens;
+		Err(Duration::from_secs_f64(missing * 60.0 / self.per_minute))
+	}
+}
+
+struct Seen {
+	events: HashMap<u64, Instant>,
+	swept: Instant,
+}
+
+/// Remembers recently accepted events so repeats within the window are caught
+pub struct Deduplicator {
+	window: Duration,
+	state: Mutex<Seen>,
+}
+
+impl Deduplicator {
+	pub fn new(window: Duration) -> Self {
+		Deduplicator {
+			window,
+			state: Mutex::new(Seen {
+				events: HashMap::new(),
+				swept: Instant::now(),
+			}),
+		}
+	}
+
+	pub fn is_duplicate(&self, key: u64, now: Instant) -> bool {
+		match self.state.lock().unwrap().events.get(&key) {
+			Some(seen) => now.duration_since(*seen) < self.window,
+			None => false,
+		}
+	}
+
+	pub fn remember(&self, keys: &[u64], now: Instant) {
+		if self.window.is_zero() {
+			return;
+		}
+
+		let mut state = self.state.lock().unwrap();
+		if now.duration_since(state.swept) >= SWEEP_INTERVAL {
+			let window = self.window;
+			state
+				.events
+				.retain(|_, seen| now.duration_since(*seen) < window);
+			state.swept = now;
+		}
+
+		for key in keys {
+			state.events.insert(*key, now);
+		}
+	}
+}
+
+/// Abuse protection for the ingest endpoint, shared by every request
+pub struct IngestLimits {
+	/// Events per minute from a single client address
+	pub ip: RateLimiter,
+	/// Events per minute from a single device
+	pub device: RateLimiter,
+	pub recent: Deduplicator,
+}
+
+impl IngestLimits {
+	pub fn from_config(config: &RuntimeConfig) -> Self {
+		IngestLimits {
+			ip: RateLimiter::new(config.ingest_ip_rate_limit),
+			device: RateLimiter::new(config.ingest_device_rate_limit),
+			recent: Deduplicator::new(Duration::from_secs(config.ingest_dedup_window)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn buckets_refill_over_time() {
+		let limiter = RateLimiter::new(60);
+		let now = Instant::now();
+
+		assert!(limiter.acquire("203.0.113.7", 50, now).is_ok());
+		assert_eq!(
+			limiter.acquire("203.0.113.7", 20, now),
+			Err(Duration::from_secs(10))
+		);
+
+		// Other keys have their own allowance
+		assert!(limiter.acquire("198.51.100.2", 60, now).is_ok());
+
+		let later = now + Duration::from_secs(10);
+		assert!(limiter.acquire("203.0.113.7", 20, later).is_ok());
+		assert!(limiter.acquire("203.0.113.7", 1, later).is_err());
+	}
+
+	#[test]
+	fn duplicates_expire_with_the_window() {
+		let recent = Deduplicator::new(Duration::from_secs(300));
+		let now = Instant::now();
+
+		assert!(!recent.is_duplicate(1, now));
+		recent.remember(&[1], now);
+		assert!(recent.is_duplicate(1, now + Duration::from_secs(299)));
+		assert!(!recent.is_duplicate(1, now + Duration::from_secs(300)));
+		assert!(!recent.is_duplicate(2, now));
+	}
+}
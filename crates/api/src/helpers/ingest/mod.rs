@@ -0,0 +1,513 @@
+use crate::{metrics::Metrics, utility::RuntimeConfig};
+use anyhow::Result;
+use std::{
+	cmp::min,
+	path::PathBuf,
+	sync::{Arc, Mutex as StdMutex, PoisonError},
+	time::Duration,
+};
+use tokio::{
+	sync::{watch, Mutex, Notify},
+	task::{spawn_blocking, JoinHandle},
+	time::sleep,
+};
+use tracing::{error, info, warn};
+
+mod event;
+mod limits;
+mod sinks;
+mod spool;
+
+pub use self::event::*;
+pub use self::limits::*;
+pub use self::sinks::*;
+pub use self::spool::*;
+
+/// Size a segment grows to before the spool starts writing the next one
+const SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Settings for the ingest queue, mostly taken from the runtime config
+#[derive(Clone, Debug)]
+pub struct IngestOptions {
+	pub spool_dir: PathBuf,
+	/// Most events kept waiting before new ones are dropped
+	pub capacity: usize,
+	/// Most events delivered to a sink at once
+	pub batch_size: usize,
+	pub segment_bytes: u64,
+	/// Delay before the first retry, doubled after each failure up to `retry_max`
+	pub retry_min: Duration,
+	pub retry_max: Duration,
+}
+
+impl IngestOptions {
+	pub fn from_config(config: &RuntimeConfig) -> Self {
+		IngestOptions {
+			spool_dir: PathBuf::from(&config.ingest_spool_dir),
+			capacity: config.ingest_queue_capacity,
+			batch_size: config.ingest_batch_size,
+			segment_bytes: SEGMENT_BYTES,
+			retry_min: Duration::from_secs(1),
+			retry_max: Duration::from_secs(60),
+		}
+	}
+}
+
+/// What happened to events handed to the queue
+#[derive(Debug, PartialEq, Eq)]
+pub enum Enqueued {
+	/// Spooled to disk and waiting for delivery
+	Accepted,
+	/// Dropped since the queue is at capacity
+	Full,
+}
+
+/// Durable queue that delivers download events to the sinks in the background
+/// Events are spooled to disk before they're acknowledged, so they survive
+/// sink outages and restarts, and are delivered to every sink at least once
+/// Each sink is delivered to by its own worker, so one that's down doesn't
+/// hold back the others
+pub struct IngestQueue {
+	spool: Arc<StdMutex<Spool>>,
+	capacity: usize,
+	/// Wakes up each worker, in the same order as the sinks
+	notify: Vec<Arc<Notify>>,
+	metrics: Arc<Metrics>,
+	stop: watch::Sender<bool>,
+	workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Names sinks are told apart by, numbering repeated kinds of sink
+/// The spool keeps each sink's cursor under its name
+pub fn sink_names(kinds: &[&str]) -> Vec<String> {
+	let mut names = Vec::new();
+	for (index, kind) in kinds.iter().enumerate() {
+		let repeats = kinds[..index].iter().filter(|other| *other == kind).count();
+
+		names.push(match repeats {
+			0 => kind.to_string(),
+			_ => format!("{}-{}", kind, repeats),
+		});
+	}
+
+	names
+}
+
+impl IngestQueue {
+	/// Opens the spool and starts delivering whatever it already holds
+	pub fn start(options: IngestOptions, sinks: Vec<Sink>, metrics: Arc<Metrics>) -> Result<Self> {
+		let spool = Spool::open(
+			&options.spool_dir,
+			options.segment_bytes,
+			&sink_names(&sinks.iter().map(Sink::name).collect::<Vec<&str>>()),
+		)?;
+
+		if spool.depth() > 0 {
+			info!(
+				target: "ingest",
+				"Resuming delivery of {} spooled events",
+				spool.depth()
+			);
+		}
+
+		metrics.set_ingest_depth(spool.depth());
+
+		let spool = Arc::new(StdMutex::new(spool));
+		let (stop, stopped) = watch::channel(false);
+		let mut notify = Vec::new();
+		let mut workers = Vec::new();
+
+		for (index, sink) in sinks.into_iter().enumerate() {
+			let wake = Arc::new(Notify::new());
+			let worker = Worker {
+				index,
+				sink,
+				spool: spool.clone(),
+				notify: wake.clone(),
+				metrics: metrics.clone(),
+				stopped: stopped.clone(),
+				options: options.clone(),
+			};
+
+			notify.push(wake);
+			workers.push(tokio::spawn(worker.run()));
+		}
+
+		Ok(IngestQueue {
+			spool,
+			capacity: options.capacity,
+			notify,
+			metrics,
+			stop,
+			workers: Mutex::new(workers),
+		})
+	}
+
+	/// Spools events for delivery, unless that would exceed the capacity
+	pub async fn enqueue(&self, events: &[DownloadEvent]) -> Result<Enqueued> {
+		let capacity = self.capacity;
+		let metrics = self.metrics.clone();
+		let events = events.to_vec();
+
+		let enqueued = with_spool(&self.spool, move |spool| {
+			if spool.depth() + events.len() > capacity {
+				metrics.observe_ingest_drop(events.len());
+				return Ok(Enqueued::Full);
+			}
+
+			spool.append(&events)?;
+			metrics.set_ingest_depth(spool.depth());
+			Ok(Enqueued::Accepted)
+		})
+		.await?;
+
+		if enqueued == Enqueued::Accepted {
+			for notify in &self.notify {
+				notify.notify_one();
+			}
+		}
+
+		Ok(enqueued)
+	}
+
+	/// Number of events the furthest behind sink is waiting for
+	pub async fn depth(&self) -> Result<usize> {
+		with_spool(&self.spool, |spool| Ok(spool.depth())).await
+	}
+
+	/// Stops delivering, anything still spooled is picked up on the next start
+	pub async fn close(&self) {
+		let _ = self.stop.send(true);
+
+		for worker in self.workers.lock().await.drain(..) {
+			if let Err(e) = worker.await {
+				error!(target: "ingest", "Delivery worker panicked: {}", e);
+			}
+		}
+	}
+}
+
+/// Runs `f` on a blocking thread, since the spool does file I/O under its lock
+/// A panic while the lock was held leaves the files intact, so it's recovered
+async fn with_spool<T, F>(spool: &Arc<StdMutex<Spool>>, f: F) -> Result<T>
+where
+	T: Send + 'static,
+	F: FnOnce(&mut Spool) -> Result<T> + Send + 'static,
+{
+	let spool = spool.clone();
+	spawn_blocking(move || f(&mut spool.lock().unwrap_or_else(PoisonError::into_inner))).await?
+}
+
+/// Background task that drains the spool into a single sink
+struct Worker {
+	/// Position of the sink, which picks its cursor in the spool
+	index: usize,
+	sink: Sink,
+	spool: Arc<StdMutex<Spool>>,
+	notify: Arc<Notify>,
+	metrics: Arc<Metrics>,
+	stopped: watch::Receiver<bool>,
+	options: IngestOptions,
+}
+
+impl Worker {
+	async fn run(mut self) {
+		let mut backoff = self.options.retry_min;
+
+		// Kept across retries so the same events are sent until they're accepted
+		let mut pending: Option<Batch> = None;
+
+		while !*self.stopped.borrow() {
+			let batch = match pending.take() {
+				Some(batch) => batch,
+				None => {
+					let (index, batch_size) = (self.index, self.options.batch_size);
+					let batch =
+						with_spool(&self.spool, move |spool| spool.peek(index, batch_size)).await;
+
+					let batch = match batch {
+						Ok(batch) => batch,
+						Err(e) => {
+							error!(target: "ingest", "Failed to read the spool: {}", e);
+							if !self.wait(backoff).await {
+								break;
+							}
+
+							backoff = min(backoff * 2, self.options.retry_max);
+							continue;
+						}
+					};
+
+					if batch.events.is_empty() && batch.skipped == 0 {
+						tokio::select! {
+							_ = self.notify.notified() => continue,
+							_ = self.stopped.changed() => break,
+						}
+					}
+
+					batch
+				}
+			};
+
+			// Batches of nothing but corrupt lines only need to be committed
+			if !batch.events.is_empty() {
+				let delivered = tokio::select! {
+					delivered = self.sink.deliver(&batch.events) => delivered,
+					_ = self.stopped.changed() => break,
+				};
+
+				self.metrics
+					.observe_ingest_forward(self.sink.name(), delivered.is_ok());
+
+				if let Err(e) = delivered {
+					warn!(
+						target: "ingest",
+						"Failed to deliver {} events to {}, retrying in {}s: {}",
+						batch.events.len(),
+						self.sink.name(),
+						backoff.as_secs(),
+						e
+					);
+
+					pending = Some(batch);
+					if !self.wait(backoff).await {
+						break;
+					}
+
+					backoff = min(backoff * 2, self.options.retry_max);
+					continue;
+				}
+			}
+
+			let index = self.index;
+			let metrics = self.metrics.clone();
+			let committed = with_spool(&self.spool, move |spool| {
+				let committed = spool.commit(index, &batch);
+				metrics.set_ingest_depth(spool.depth());
+				committed
+			})
+			.await;
+
+			if let Err(e) = committed {
+				// The batch is sent again, which at least once delivery allows
+				error!(target: "ingest", "Failed to commit delivered events: {}", e);
+			}
+
+			backoff = self.options.retry_min;
+		}
+	}
+
+	/// Sleeps before a retry, returning false if the queue is closed meanwhile
+	async fn wait(&mut self, delay: Duration) -> bool {
+		tokio::select! {
+			_ = sleep(delay) => true,
+			_ = self.stopped.changed() => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+	use reqwest::Client;
+	use std::{
+		fs::{read_dir, read_to_string},
+		net::SocketAddr,
+		sync::atomic::{AtomicUsize, Ordering},
+	};
+	use tempfile::tempdir;
+	use tokio::time::{timeout, Instant};
+
+	/// Stands in for Vector, rejecting the first `failures` requests
+	#[derive(Clone, Default)]
+	struct FakeVector {
+		failures: Arc<AtomicUsize>,
+		received: Arc<std::sync::Mutex<Vec<DownloadEvent>>>,
+	}
+
+	async fn receive(
+		State(vector): State<FakeVector>,
+		Json(events): Json<Vec<DownloadEvent>>,
+	) -> StatusCode {
+		let failures = vector.failures.load(Ordering::SeqCst);
+		if failures > 0 {
+			vector.failures.store(failures - 1, Ordering::SeqCst);
+			return StatusCode::SERVICE_UNAVAILABLE;
+		}
+
+		vector.received.lock().unwrap().extend(events);
+		StatusCode::OK
+	}
+
+	async fn spawn_vector(vector: FakeVector) -> SocketAddr {
+		let app = Router::new().route("/", post(receive)).with_state(vector);
+
+		let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+			.serve(app.into_make_service());
+
+		let addr = server.local_addr();
+		tokio::spawn(server);
+		addr
+	}
+
+	fn vector_sink(url: &str) -> Sink {
+		Sink::Vector(VectorSink::new(Client::new(), url))
+	}
+
+	fn events(count: usize) -> Vec<DownloadEvent> {
+		(0..count)
+			.map(|id| DownloadEvent::example(&id.to_string()))
+			.collect()
+	}
+
+	fn options(spool_dir: PathBuf, capacity: usize) -> IngestOptions {
+		IngestOptions {
+			spool_dir,
+			capacity,
+			batch_size: 2,
+			segment_bytes: 64,
+			retry_min: Duration::from_millis(10),
+			retry_max: Duration::from_millis(40),
+		}
+	}
+
+	async fn wait_until_drained(queue: &IngestQueue) {
+		let started = Instant::now();
+		while queue.depth().await.unwrap() > 0 {
+			assert!(
+				started.elapsed() < Duration::from_secs(5),
+				"Queue never drained"
+			);
+			sleep(Duration::from_millis(10)).await;
+		}
+	}
+
+	#[tokio::test]
+	async fn delivers_through_outages_and_restarts() {
+		let dir = tempdir().unwrap();
+		let metrics = Arc::new(Metrics::new().unwrap());
+		let events = events(5);
+
+		// Nothing listens here, so every event stays spooled
+		let queue = IngestQueue::start(
+			options(dir.path().to_path_buf(), 100),
+			vec![vector_sink("http://127.0.0.1:9/")],
+			metrics.clone(),
+		)
+		.unwrap();
+
+		assert_eq!(queue.enqueue(&events).await.unwrap(), Enqueued::Accepted);
+		timeout(Duration::from_secs(5), queue.close())
+			.await
+			.unwrap();
+
+		let vector = FakeVector::default();
+		vector.failures.store(2, Ordering::SeqCst);
+		let addr = spawn_vector(vector.clone()).await;
+
+		let queue = IngestQueue::start(
+			options(dir.path().to_path_buf(), 100),
+			vec![vector_sink(&format!("http://{addr}/"))],
+			metrics.clone(),
+		)
+		.unwrap();
+
+		assert_eq!(queue.depth().await.unwrap(), 5);
+		wait_until_drained(&queue).await;
+		queue.close().await;
+
+		assert_eq!(*vector.received.lock().unwrap(), events);
+		assert_eq!(vector.failures.load(Ordering::SeqCst), 0);
+	}
+
+	#[tokio::test]
+	async fn drops_events_past_capacity() {
+		let dir = tempdir().unwrap();
+		let metrics = Arc::new(Metrics::new().unwrap());
+		let queue = IngestQueue::start(
+			options(dir.path().to_path_buf(), 3),
+			vec![vector_sink("http://127.0.0.1:9/")],
+			metrics.clone(),
+		)
+		.unwrap();
+
+		let events = events(2);
+		assert_eq!(queue.enqueue(&events).await.unwrap(), Enqueued::Accepted);
+		assert_eq!(queue.enqueue(&events).await.unwrap(), Enqueued::Full);
+		assert_eq!(queue.depth().await.unwrap(), 2);
+		queue.close().await;
+	}
+
+	#[tokio::test]
+	async fn healthy_sinks_are_not_sent_retries() {
+		let spool = tempdir().unwrap();
+		let files = tempdir().unwrap();
+		let vector = FakeVector::default();
+		vector.failures.store(3, Ordering::SeqCst);
+		let addr = spawn_vector(vector.clone()).await;
+
+		let queue = IngestQueue::start(
+			options(spool.path().to_path_buf(), 100),
+			vec![
+				Sink::File(FileSink::open(files.path(), FILE_ROTATE_BYTES).unwrap()),
+				vector_sink(&format!("http://{addr}/")),
+			],
+			Arc::new(Metrics::new().unwrap()),
+		)
+		.unwrap();
+
+		let events = events(2);
+		assert_eq!(queue.enqueue(&events).await.unwrap(), Enqueued::Accepted);
+		wait_until_drained(&queue).await;
+		queue.close().await;
+
+		let written = read_dir(files.path())
+			.unwrap()
+			.map(|entry| read_to_string(entry.unwrap().path()).unwrap())
+			.collect::<String>();
+
+		assert_eq!(written.lines().count(), 2);
+		assert_eq!(*vector.received.lock().unwrap(), events);
+	}
+
+	#[tokio::test]
+	async fn failing_sinks_do_not_hold_back_the_others() {
+		let spool = tempdir().unwrap();
+		let files = tempdir().unwrap();
+
+		let queue = IngestQueue::start(
+			options(spool.path().to_path_buf(), 100),
+			vec![
+				vector_sink("http://127.0.0.1:9/"),
+				Sink::File(FileSink::open(files.path(), FILE_ROTATE_BYTES).unwrap()),
+			],
+			Arc::new(Metrics::new().unwrap()),
+		)
+		.unwrap();
+
+		assert_eq!(queue.enqueue(&events(5)).await.unwrap(), Enqueued::Accepted);
+
+		let started = Instant::now();
+		loop {
+			let written = read_dir(files.path())
+				.unwrap()
+				.map(|entry| read_to_string(entry.unwrap().path()).unwrap())
+				.collect::<String>();
+
+			if written.lines().count() == 5 {
+				break;
+			}
+
+			assert!(
+				started.elapsed() < Duration::from_secs(5),
+				"File sink never caught up"
+			);
+			sleep(Duration::from_millis(10)).await;
+		}
+
+		// The events stay spooled for the sink that's down
+		assert_eq!(queue.depth().await.unwrap(), 5);
+		queue.close().await;
+	}
+}
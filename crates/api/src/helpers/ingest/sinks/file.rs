@@ -0,0 +1,129 @@
+use super::EventSink;
+use crate::helpers::ingest::DownloadEvent;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_json::to_string;
+use std::{
+	fs::create_dir_all,
+	path::{Path, PathBuf},
+};
+use tokio::{
+	fs::{File, OpenOptions},
+	io::AsyncWriteExt,
+	sync::Mutex,
+};
+
+/// Size a file grows to before the sink moves on to the next one
+pub const FILE_ROTATE_BYTES: u64 = 64 * 1024 * 1024;
+
+struct Current {
+	file: Option<File>,
+	written: u64,
+	/// Files opened so far, keeping names unique within the same millisecond
+	opened: u64,
+}
+
+/// Appends events to newline-delimited JSON files in a directory
+/// Files are named after when they were started and rotated by size, so
+/// finished ones can be shipped or removed while the sink keeps writing
+pub struct FileSink {
+	dir: PathBuf,
+	rotate_bytes: u64,
+	current: Mutex<Current>,
+}
+
+impl FileSink {
+	pub fn open(dir: &Path, rotate_bytes: u64) -> Result<Self> {
+		create_dir_all(dir)
+			.with_context(|| format!("Failed to create sink directory {}", dir.display()))?;
+
+		Ok(FileSink {
+			dir: dir.to_path_buf(),
+			rotate_bytes,
+			current: Mutex::new(Current {
+				file: None,
+				written: 0,
+				opened: 0,
+			}),
+		})
+	}
+}
+
+impl EventSink for FileSink {
+	async fn deliver(&self, events: &[DownloadEvent]) -> Result<()> {
+		let mut buffer = String::new();
+		for event in events {
+			buffer.push_str(&to_string(event)?);
+			buffer.push('\n');
+		}
+
+		let mut current = self.current.lock().await;
+		if current.file.is_none() || current.written >= self.rotate_bytes {
+			let name = format!(
+				"downloads-{}-{}.jsonl",
+				Utc::now().format("%Y%m%dT%H%M%S%3f"),
+				current.opened
+			);
+
+			let file = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(self.dir.join(name))
+				.await?;
+
+			current.file = Some(file);
+			current.written = 0;
+			current.opened += 1;
+		}
+
+		if let Some(file) = current.file.as_mut() {
+			file.write_all(buffer.as_bytes()).await?;
+			file.flush().await?;
+		}
+
+		current.written += buffer.len() as u64;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs::{read_dir, read_to_string};
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn rotates_files_by_size() {
+		let dir = tempdir().unwrap();
+		let sink = FileSink::open(dir.path(), 1).unwrap();
+
+		let events = vec![
+			DownloadEvent::example("ws.hbang.common"),
+			DownloadEvent::example("com.tigisoftware.filza"),
+		];
+
+		sink.deliver(&events[..1]).await.unwrap();
+		sink.deliver(&events[1..]).await.unwrap();
+
+		let mut files = read_dir(dir.path())
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.collect::<Vec<PathBuf>>();
+
+		files.sort();
+		assert_eq!(files.len(), 2);
+
+		let written = files
+			.iter()
+			.flat_map(|file| {
+				read_to_string(file)
+					.unwrap()
+					.lines()
+					.map(|line| serde_json::from_str::<DownloadEvent>(line).unwrap())
+					.collect::<Vec<DownloadEvent>>()
+			})
+			.collect::<Vec<DownloadEvent>>();
+
+		assert_eq!(written, events);
+	}
+}
@@ -0,0 +1,53 @@
+use super::EventSink;
+use crate::helpers::ingest::DownloadEvent;
+use anyhow::Result;
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde::Serialize;
+use url::Url;
+
+/// Content type of JSON records in the Kafka REST proxy v2 API
+const KAFKA_JSON_CONTENT_TYPE: &str = "application/vnd.kafka.json.v2+json";
+
+#[derive(Serialize)]
+struct Record<'a> {
+	value: &'a DownloadEvent,
+}
+
+#[derive(Serialize)]
+struct Records<'a> {
+	records: Vec<Record<'a>>,
+}
+
+/// Produces events to a topic through a Kafka REST proxy
+/// Works with the Confluent REST Proxy and Redpanda's HTTP proxy alike
+pub struct KafkaSink {
+	http: Client,
+	url: Url,
+}
+
+impl KafkaSink {
+	pub fn new(http: Client, url: &Url) -> Self {
+		KafkaSink {
+			http,
+			url: url.clone(),
+		}
+	}
+}
+
+impl EventSink for KafkaSink {
+	async fn deliver(&self, events: &[DownloadEvent]) -> Result<()> {
+		let records = Records {
+			records: events.iter().map(|value| Record { value }).collect(),
+		};
+
+		self.http
+			.post(self.url.clone())
+			.header(CONTENT_TYPE, KAFKA_JSON_CONTENT_TYPE)
+			.body(serde_json::to_vec(&records)?)
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,30 @@
+use super::EventSink;
+use crate::helpers::ingest::DownloadEvent;
+use anyhow::Result;
+use serde_json::to_string;
+use std::io::{stdout, Write};
+use tokio::task::spawn_blocking;
+
+/// Prints every event as a line of JSON, interleaved with the logs
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+	async fn deliver(&self, events: &[DownloadEvent]) -> Result<()> {
+		let mut buffer = String::new();
+		for event in events {
+			buffer.push_str(&to_string(event)?);
+			buffer.push('\n');
+		}
+
+		// Written under the lock on a blocking thread, so lines from the logs
+		// can't land in the middle of an event
+		spawn_blocking(move || {
+			let mut stdout = stdout().lock();
+			stdout.write_all(buffer.as_bytes())?;
+			stdout.flush()
+		})
+		.await??;
+
+		Ok(())
+	}
+}
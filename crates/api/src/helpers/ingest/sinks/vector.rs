@@ -0,0 +1,32 @@
+use super::EventSink;
+use crate::helpers::ingest::DownloadEvent;
+use anyhow::Result;
+use reqwest::Client;
+
+/// Sends events to Vector's HTTP source as a JSON array
+pub struct VectorSink {
+	http: Client,
+	url: String,
+}
+
+impl VectorSink {
+	pub fn new(http: Client, url: &str) -> Self {
+		VectorSink {
+			http,
+			url: url.to_string(),
+		}
+	}
+}
+
+impl EventSink for VectorSink {
+	async fn deliver(&self, events: &[DownloadEvent]) -> Result<()> {
+		self.http
+			.post(&self.url)
+			.json(events)
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,430 @@
+use super::DownloadEvent;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{from_str, to_string};
+use std::{
+	collections::VecDeque,
+	fs::{create_dir_all, read_dir, read_to_string, remove_file, rename, File, OpenOptions},
+	io::{BufRead, BufReader, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Position in the spool, as a segment and a byte offset into it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+	pub segment: u64,
+	pub offset: u64,
+}
+
+/// Events read from the spool, along with where the next read starts
+pub struct Batch {
+	pub events: Vec<DownloadEvent>,
+	/// Lines that couldn't be parsed, skipped but still consumed
+	pub skipped: usize,
+	pub next: Position,
+}
+
+/// Append-only log of events waiting for delivery, split into segment files
+/// Each segment holds one JSON event per line and is deleted once every sink
+/// has read past it, since each sink reads the spool through its own cursor
+/// Segments are never reopened for writing, so a torn write from a crash
+/// only ever sits at the end of a segment that is no longer appended to
+pub struct Spool {
+	dir: PathBuf,
+	segment_bytes: u64,
+	segments: VecDeque<u64>,
+	writer: File,
+	written: u64,
+	/// Set when a write failed partway, so the next one starts a new segment
+	torn: bool,
+	/// Names and positions of the sinks reading the spool, in the same order
+	sinks: Vec<String>,
+	cursors: Vec<Position>,
+	/// Events each sink hasn't been delivered yet
+	pending: Vec<usize>,
+}
+
+fn segment_name(segment: u64) -> String {
+	format!("{:020}.jsonl", segment)
+}
+
+fn cursor_name(sink: &str) -> String {
+	format!("cursor-{}", sink)
+}
+
+fn read_cursor(path: &Path) -> Result<Option<Position>> {
+	match read_to_string(path) {
+		Ok(cursor) => match parse_cursor(&cursor) {
+			Some(cursor) => Ok(Some(cursor)),
+			None => Err(anyhow!("Invalid spool cursor in {}", path.display())),
+		},
+		Err(_) => Ok(None),
+	}
+}
+
+impl Spool {
+	/// Opens the spool in `dir` for the named sinks, picking up the events left
+	/// by a previous run
+	pub fn open(dir: &Path, segment_bytes: u64, sinks: &[String]) -> Result<Self> {
+		create_dir_all(dir)
+			.with_context(|| format!("Failed to create spool directory {}", dir.display()))?;
+
+		let mut segments = Vec::new();
+		for entry in read_dir(dir)? {
+			let name = entry?.file_name();
+			let segment = name
+				.to_str()
+				.and_then(|name| name.strip_suffix(".jsonl"))
+				.and_then(|segment| segment.parse::<u64>().ok());
+
+			if let Some(segment) = segment {
+				segments.push(segment);
+			}
+		}
+
+		segments.sort_unstable();
+
+		let mut cursors = Vec::new();
+		for sink in sinks {
+			cursors.push(read_cursor(&dir.join(cursor_name(sink)))?.unwrap_or_default());
+		}
+
+		// Segments behind every cursor were delivered before they could be removed
+		let oldest = cursors.iter().map(|cursor| cursor.segment).min();
+		let oldest = oldest.unwrap_or_default();
+		for segment in segments.iter().filter(|segment| **segment < oldest) {
+			remove_file(dir.join(segment_name(*segment)))?;
+		}
+
+		segments.retain(|segment| *segment >= oldest);
+
+		let newest = cursors.iter().map(|cursor| cursor.segment).max();
+		let active = match segments.last() {
+			Some(last) => last + 1,
+			None => newest.unwrap_or_default(),
+		};
+
+		let writer = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(dir.join(segment_name(active)))?;
+
+		segments.push(active);
+
+		// Cursors pointing at a segment that's gone start on the one after it
+		for cursor in &mut cursors {
+			let segment = segments
+				.iter()
+				.find(|segment| **segment >= cursor.segment)
+				.copied()
+				.unwrap_or(active);
+
+			if segment != cursor.segment {
+				*cursor = Position { segment, offset: 0 };
+			}
+		}
+
+		let mut spool = Spool {
+			dir: dir.to_path_buf(),
+			segment_bytes,
+			segments: segments.into(),
+			writer,
+			written: 0,
+			torn: false,
+			sinks: sinks.to_vec(),
+			cursors,
+			pending: Vec::new(),
+		};
+
+		spool.pending = spool
+			.cursors
+			.iter()
+			.map(|cursor| spool.count_pending(*cursor))
+			.collect::<Result<Vec<usize>>>()?;
+
+		Ok(spool)
+	}
+
+	/// Number of events the furthest behind sink hasn't been delivered yet
+	pub fn depth(&self) -> usize {
+		self.pending.iter().copied().max().unwrap_or_default()
+	}
+
+	/// Appends events to the active segment, starting a new one when it's full
+	/// Events are synced to disk before this returns, so they can be acknowledged
+	pub fn append(&mut self, events: &[DownloadEvent]) -> Result<()> {
+		let mut buffer = String::new();
+		for event in events {
+			buffer.push_str(&to_string(event)?);
+			buffer.push('\n');
+		}
+
+		// Whatever a failed write left behind is sealed off, like after a crash,
+		// so it can't be glued onto the start of these events
+		if self.torn {
+			self.next_segment()?;
+			self.torn = false;
+		}
+
+		let written = self
+			.writer
+			.write_all(buffer.as_bytes())
+			.and_then(|_| self.writer.sync_data());
+
+		if let Err(e) = written {
+			self.torn = true;
+			return Err(e.into());
+		}
+
+		self.written += buffer.len() as u64;
+		for pending in &mut self.pending {
+			*pending += events.len();
+		}
+
+		if self.written >= self.segment_bytes {
+			self.next_segment()?;
+		}
+
+		Ok(())
+	}
+
+	/// Seals the active segment and starts appending to a new one
+	fn next_segment(&mut self) -> Result<()> {
+		let segment = self.active() + 1;
+		self.writer = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(self.dir.join(segment_name(segment)))?;
+
+		self.segments.push_back(segment);
+		self.written = 0;
+		Ok(())
+	}
+
+	/// Reads up to `max` events from a sink's cursor without consuming them
+	pub fn peek(&self, sink: usize, max: usize) -> Result<Batch> {
+		let mut batch = Batch {
+			events: Vec::new(),
+			skipped: 0,
+			next: self.cursors[sink],
+		};
+
+		while batch.events.len() < max {
+			let mut reader =
+				BufReader::new(File::open(self.dir.join(segment_name(batch.next.segment)))?);
+
+			reader.seek(SeekFrom::Start(batch.next.offset))?;
+
+			let mut line = String::new();
+			while batch.events.len() < max {
+				line.clear();
+				let read = reader.read_line(&mut line)?;
+				if read == 0 {
+					break;
+				}
+
+				// Only the active segment may end in a line still being written
+				if !line.ends_with('\n') && batch.next.segment == self.active() {
+					break;
+				}
+
+				batch.next.offset += read as u64;
+				match from_str::<DownloadEvent>(line.trim_end()) {
+					Ok(event) => batch.events.push(event),
+					Err(e) => {
+						warn!(target: "ingest", "Skipping a corrupt spooled event: {}", e);
+						batch.skipped += 1;
+					}
+				}
+			}
+
+			if batch.events.len() >= max || batch.next.segment == self.active() {
+				break;
+			}
+
+			// Move on once a sealed segment has been read to its end
+			match self
+				.segments
+				.iter()
+				.find(|segment| **segment > batch.next.segment)
+			{
+				Some(segment) => {
+					batch.next = Position {
+						segment: *segment,
+						offset: 0,
+					}
+				}
+				None => break,
+			}
+		}
+
+		Ok(batch)
+	}
+
+	/// Marks a batch as delivered to a sink, removing the segments that every
+	/// sink has finished
+	pub fn commit(&mut self, sink: usize, batch: &Batch) -> Result<()> {
+		self.write_cursor(sink, batch.next)?;
+		self.cursors[sink] = batch.next;
+		self.pending[sink] = self.pending[sink].saturating_sub(batch.events.len() + batch.skipped);
+
+		let oldest = self.cursors.iter().map(|cursor| cursor.segment).min();
+		let oldest = oldest.unwrap_or(batch.next.segment);
+		while let Some(segment) = self.segments.front().copied() {
+			if segment >= oldest {
+				break;
+			}
+
+			remove_file(self.dir.join(segment_name(segment)))?;
+			self.segments.pop_front();
+		}
+
+		Ok(())
+	}
+
+	fn active(&self) -> u64 {
+		self.segments.back().copied().unwrap_or_default()
+	}
+
+	/// Replaces a sink's cursor file in a single rename so it's never half written
+	fn write_cursor(&self, sink: usize, position: Position) -> Result<()> {
+		let name = cursor_name(&self.sinks[sink]);
+		let temporary = self.dir.join(format!("{}.tmp", name));
+		let mut file = File::create(&temporary)?;
+		write!(file, "{} {}", position.segment, position.offset)?;
+		file.sync_all()?;
+
+		rename(temporary, self.dir.join(name))?;
+		Ok(())
+	}
+
+	fn count_pending(&self, cursor: Position) -> Result<usize> {
+		let mut pending = 0;
+		for segment in self
+			.segments
+			.iter()
+			.filter(|segment| **segment >= cursor.segment)
+		{
+			let mut reader = BufReader::new(File::open(self.dir.join(segment_name(*segment)))?);
+			if *segment == cursor.segment {
+				reader.seek(SeekFrom::Start(cursor.offset))?;
+			}
+
+			pending += reader.lines().count();
+		}
+
+		Ok(pending)
+	}
+}
+
+fn parse_cursor(cursor: &str) -> Option<Position> {
+	let (segment, offset) = cursor.trim().split_once(' ')?;
+	Some(Position {
+		segment: segment.parse().ok()?,
+		offset: offset.parse().ok()?,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::tempdir;
+
+	fn event(id: usize) -> DownloadEvent {
+		DownloadEvent::example(&id.to_string())
+	}
+
+	fn sinks(names: &[&str]) -> Vec<String> {
+		names.iter().map(|name| name.to_string()).collect()
+	}
+
+	#[test]
+	fn events_survive_reopening() {
+		let dir = tempdir().unwrap();
+		let mut spool = Spool::open(dir.path(), 64, &sinks(&["vector"])).unwrap();
+
+		for id in 0..10 {
+			spool.append(&[event(id)]).unwrap();
+		}
+
+		assert_eq!(spool.depth(), 10);
+		let batch = spool.peek(0, 4).unwrap();
+		assert_eq!(batch.events[0], event(0));
+		spool.commit(0, &batch).unwrap();
+		assert_eq!(spool.depth(), 6);
+		drop(spool);
+
+		// Appending after a reopen goes to a fresh segment
+		let mut spool = Spool::open(dir.path(), 64, &sinks(&["vector"])).unwrap();
+		assert_eq!(spool.depth(), 6);
+		spool.append(&[event(10)]).unwrap();
+
+		let batch = spool.peek(0, 100).unwrap();
+		assert_eq!(batch.events, (4..=10).map(event).collect::<Vec<_>>());
+		spool.commit(0, &batch).unwrap();
+		assert_eq!(spool.depth(), 0);
+		assert!(spool.peek(0, 100).unwrap().events.is_empty());
+
+		// Only the active segment and the cursor are left behind
+		assert_eq!(read_dir(dir.path()).unwrap().count(), 2);
+	}
+
+	#[test]
+	fn sinks_read_through_their_own_cursors() {
+		let dir = tempdir().unwrap();
+		let names = sinks(&["file", "vector"]);
+		let mut spool = Spool::open(dir.path(), 64, &names).unwrap();
+
+		for id in 0..6 {
+			spool.append(&[event(id)]).unwrap();
+		}
+
+		let batch = spool.peek(0, 100).unwrap();
+		spool.commit(0, &batch).unwrap();
+		assert_eq!(spool.depth(), 6);
+
+		// Segments are kept until the slower sink has read them too
+		let batch = spool.peek(1, 2).unwrap();
+		assert_eq!(batch.events, vec![event(0), event(1)]);
+		spool.commit(1, &batch).unwrap();
+		assert_eq!(spool.depth(), 4);
+		drop(spool);
+
+		let spool = Spool::open(dir.path(), 64, &names).unwrap();
+		assert!(spool.peek(0, 100).unwrap().events.is_empty());
+		assert_eq!(
+			spool.peek(1, 100).unwrap().events,
+			(2..6).map(event).collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn torn_writes_are_skipped() {
+		let dir = tempdir().unwrap();
+		let mut spool = Spool::open(dir.path(), 1024, &sinks(&["vector"])).unwrap();
+		spool.append(&[event(0)]).unwrap();
+		spool.writer.write_all(b"{\"package_id\":").unwrap();
+		drop(spool);
+
+		let spool = Spool::open(dir.path(), 1024, &sinks(&["vector"])).unwrap();
+		let batch = spool.peek(0, 100).unwrap();
+		assert_eq!(batch.events, vec![event(0)]);
+		assert_eq!(batch.skipped, 1);
+	}
+
+	#[test]
+	fn appends_after_a_failed_write_start_a_new_segment() {
+		let dir = tempdir().unwrap();
+		let mut spool = Spool::open(dir.path(), 1024, &sinks(&["vector"])).unwrap();
+		spool.append(&[event(0)]).unwrap();
+
+		// Stands in for a write that ran out of space halfway through
+		spool.writer.write_all(b"{\"package_id\":").unwrap();
+		spool.torn = true;
+
+		spool.append(&[event(1)]).unwrap();
+		let batch = spool.peek(0, 100).unwrap();
+		assert_eq!(batch.events, vec![event(0), event(1)]);
+		assert_eq!(batch.skipped, 1);
+	}
+}
@@ -1,4 +1,9 @@
+pub mod apt;
+pub mod health;
+pub mod ingest;
 mod pg_client;
+pub mod resolver;
 pub mod responses;
+pub mod search;
 
 pub use self::pg_client::*;
@@ -1,25 +1,16 @@
-use crate::utility::load_runtime_config;
 use anyhow::Result;
 use deadpool_postgres::{
-	tokio_postgres::{types::Type, Row},
-	Client, Config as PgConfig, ManagerConfig, Pool, RecyclingMethod, Runtime,
+	Client, Config as PgConfig, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime,
 };
 use openssl::ssl::{SslConnector, SslMethod};
 use postgres_openssl::MakeTlsConnector;
-use serde_json::{Map, Value};
-use std::sync::OnceLock;
-
-static DB_POOL: OnceLock<Pool> = OnceLock::new();
-
-pub async fn create_db() -> Result<()> {
-	if DB_POOL.get().is_some() {
-		return Ok(());
-	}
-
-	let config = load_runtime_config();
+use tracing::{error, info, warn};
 
+/// Opens a pool with the database and waits until it answers queries
+pub async fn create_pool(database_url: &str, pool_size: usize) -> Result<Pool> {
 	let mut pg = PgConfig::new();
-	pg.url = Some(config.database_url);
+	pg.url = Some(database_url.to_string());
+	pg.pool = Some(PoolConfig::new(pool_size));
 	pg.manager = Some(ManagerConfig {
 		recycling_method: RecyclingMethod::Fast,
 	});
@@ -27,7 +18,7 @@ pub async fn create_db() -> Result<()> {
 	let ssl_builder = match SslConnector::builder(SslMethod::tls()) {
 		Ok(builder) => builder,
 		Err(e) => {
-			eprintln!("[db] Failed to create SSL builder: {}", e);
+			error!(target: "db", "Failed to create SSL builder: {}", e);
 			return Err(e.into());
 		}
 	};
@@ -36,18 +27,18 @@ pub async fn create_db() -> Result<()> {
 	let pool = match pg.create_pool(Some(Runtime::Tokio1), connector) {
 		Ok(pool) => pool,
 		Err(e) => {
-			eprintln!("[db] Failed to create pool: {}", e);
+			error!(target: "db", "Failed to create pool: {}", e);
 			return Err(e.into());
 		}
 	};
 
-	println!("[db] Opened a pool with the database");
+	info!(target: "db", "Opened a pool with the database");
 
 	// Create a client and try the health query 10 times
 	let client = match pool.get().await {
 		Ok(client) => client,
 		Err(e) => {
-			eprintln!("[db] Failed to get a client: {}", e);
+			error!(target: "db", "Failed to get a client: {}", e);
 			return Err(e.into());
 		}
 	};
@@ -55,11 +46,11 @@ pub async fn create_db() -> Result<()> {
 	for i in 1..=10 {
 		match client.query("SELECT version();", &[]).await {
 			Ok(_) => {
-				println!("[db] connected to postgres after {} attempts", i);
+				info!(target: "db", "Connected to postgres after {} attempts", i);
 				break;
 			}
 			Err(e) => {
-				eprintln!("[db] failed to connect to postgres: {}", e);
+				warn!(target: "db", "Failed to connect to postgres: {}", e);
 				if i == 10 {
 					return Err(e.into());
 				}
@@ -74,59 +65,15 @@ pub async fn create_db() -> Result<()> {
 		}
 	}
 
-	match DB_POOL.set(pool) {
-		Ok(_) => Ok(()),
-		Err(_) => {
-			eprintln!("[db] Failed to set the pool");
-			Err(anyhow::anyhow!("Failed to set the pool"))
-		}
-	}
+	Ok(pool)
 }
 
-pub async fn pg_client() -> Result<Client> {
-	let pool = match DB_POOL.get() {
-		Some(pool) => pool,
-		None => {
-			eprintln!("[db] Pool not initialized");
-			return Err(anyhow::anyhow!("Pool not initialized"));
-		}
-	};
-
+pub async fn pg_client(pool: &Pool) -> Result<Client> {
 	match pool.get().await {
 		Ok(client) => Ok(client),
 		Err(e) => {
-			eprintln!("[db] Failed to get a client: {}", e);
+			error!(target: "db", "Failed to get a client: {}", e);
 			Err(e.into())
 		}
 	}
 }
-
-pub fn row_to_value(row: &Row) -> Value {
-	let mut obj = Map::new();
-
-	for (i, column) in row.columns().iter().enumerate() {
-		let column_name = column.name();
-		let column_value = match *column.type_() {
-			Type::BOOL => row
-				.get::<usize, Option<bool>>(i)
-				.map_or(Value::Null, Into::into),
-			Type::INT2 => row
-				.get::<usize, Option<i16>>(i)
-				.map_or(Value::Null, Into::into),
-			Type::INT4 => row
-				.get::<usize, Option<i32>>(i)
-				.map_or(Value::Null, Into::into),
-			Type::INT8 => row
-				.get::<usize, Option<i64>>(i)
-				.map_or(Value::Null, Into::into),
-			Type::TEXT | Type::VARCHAR => row
-				.get::<usize, Option<String>>(i)
-				.map_or(Value::Null, Into::into),
-			_ => Value::Null,
-		};
-
-		obj.insert(column_name.to_string(), column_value);
-	}
-
-	Value::Object(obj)
-}
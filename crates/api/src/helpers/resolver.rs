@@ -0,0 +1,347 @@
+use crate::{
+	types::Unsatisfiable,
+	utility::{compare_versions, format_relation, Dependency, Relation},
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// An installable package build that the resolver can choose from
+#[derive(Debug, Serialize)]
+pub struct Candidate {
+	pub id: String,
+	pub package_id: String,
+	pub version: String,
+	pub architecture: String,
+	pub repository_id: String,
+	pub quality: i32,
+	pub package_filename: String,
+	pub package_size: i64,
+	pub sha256_hash: Option<String>,
+	pub download_url: String,
+
+	#[serde(skip)]
+	pub depends: Vec<Relation>,
+	#[serde(skip)]
+	pub pre_depends: Vec<Relation>,
+	#[serde(skip)]
+	pub conflicts: Vec<Relation>,
+	#[serde(skip)]
+	pub breaks: Vec<Relation>,
+	#[serde(skip)]
+	pub provides: Vec<Relation>,
+}
+
+impl Candidate {
+	fn satisfies(&self, dependency: &Dependency) -> bool {
+		dependency.satisfied_by(&self.package_id, &self.version, &self.provides)
+	}
+
+	/// Whether this package declares a conflict or break against another
+	fn rejects(&self, package_id: &str, version: &str, provides: &[Relation]) -> bool {
+		// Packages are allowed to conflict with what they provide
+		if package_id == self.package_id {
+			return false;
+		}
+
+		self.conflicts
+			.iter()
+			.chain(self.breaks.iter())
+			.flatten()
+			.any(|dependency| dependency.satisfied_by(package_id, version, provides))
+	}
+
+	/// Names of every package that could satisfy one of the dependencies
+	pub fn dependency_names(&self) -> impl Iterator<Item = &str> {
+		self.pre_depends
+			.iter()
+			.chain(self.depends.iter())
+			.flatten()
+			.map(|dependency| dependency.package.as_str())
+	}
+}
+
+/// A package the client already has, it satisfies dependencies on its own
+#[derive(Debug)]
+pub struct Installed {
+	pub package_id: String,
+	pub version: String,
+}
+
+/// Resolves the requested relations into an install plan
+/// Dependencies are resolved breadth-first and each one is satisfied by its
+/// first alternative with a compatible candidate, preferring the newest
+/// version and then the highest quality repository, there is no backtracking
+pub fn resolve<'a>(
+	requested: Vec<Relation>,
+	installed: &[Installed],
+	candidates: &'a [Candidate],
+) -> Result<Vec<&'a Candidate>, Vec<Unsatisfiable>> {
+	let mut plan: Vec<&Candidate> = Vec::new();
+	let mut unsatisfiable = Vec::new();
+	let mut queue: VecDeque<(Option<&str>, Relation)> = requested
+		.into_iter()
+		.map(|relation| (None, relation))
+		.collect();
+
+	while let Some((required_by, relation)) = queue.pop_front() {
+		let satisfied = relation.iter().any(|dependency| {
+			plan.iter().any(|package| package.satisfies(dependency))
+				|| installed.iter().any(|package| {
+					dependency.satisfied_by(&package.package_id, &package.version, &[])
+				})
+		});
+
+		if satisfied {
+			continue;
+		}
+
+		let mut reasons = Vec::new();
+		let chosen = relation.iter().find_map(|dependency| {
+			match choose(dependency, installed, candidates, &plan) {
+				Ok(candidate) => Some(candidate),
+				Err(reason) => {
+					reasons.push(reason);
+					None
+				}
+			}
+		});
+
+		match chosen {
+			Some(candidate) => {
+				plan.push(candidate);
+				for relation in candidate.pre_depends.iter().chain(candidate.depends.iter()) {
+					queue.push_back((Some(&candidate.package_id), relation.clone()));
+				}
+			}
+			None => unsatisfiable.push(Unsatisfiable {
+				required_by: required_by.map(|package_id| package_id.to_string()),
+				relation: format_relation(&relation),
+				reasons,
+			}),
+		}
+	}
+
+	match unsatisfiable.is_empty() {
+		true => Ok(plan),
+		false => Err(unsatisfiable),
+	}
+}
+
+/// Picks the best candidate for a dependency that fits into the plan so far
+fn choose<'a>(
+	dependency: &Dependency,
+	installed: &[Installed],
+	candidates: &'a [Candidate],
+	plan: &[&Candidate],
+) -> Result<&'a Candidate, String> {
+	// Only one version of a package can be installed at a time
+	let planned = plan
+		.iter()
+		.find(|package| package.package_id == dependency.package);
+
+	if let Some(planned) = planned {
+		return Err(format!(
+			"{} {} is already selected and does not satisfy {}",
+			planned.package_id, planned.version, dependency
+		));
+	}
+
+	let mut matches = candidates
+		.iter()
+		.filter(|candidate| candidate.satisfies(dependency))
+		.filter(|candidate| {
+			!plan
+				.iter()
+				.any(|package| package.package_id == candidate.package_id)
+		})
+		.collect::<Vec<&Candidate>>();
+
+	if matches.is_empty() {
+		let known = candidates
+			.iter()
+			.any(|candidate| candidate.package_id == dependency.package);
+
+		return Err(match known {
+			true => format!("No available version satisfies {}", dependency),
+			false => format!(
+				"{} is not available from the enabled repositories",
+				dependency.package
+			),
+		});
+	}
+
+	// Real packages are preferred over packages that only provide the name
+	matches.sort_by(|a, b| {
+		(b.package_id == dependency.package)
+			.cmp(&(a.package_id == dependency.package))
+			.then_with(|| compare_versions(&b.version, &a.version))
+			.then_with(|| a.quality.cmp(&b.quality))
+	});
+
+	let mut conflict = None;
+	for candidate in matches {
+		let planned_conflict = plan.iter().find(|package| {
+			candidate.rejects(&package.package_id, &package.version, &package.provides)
+				|| package.rejects(
+					&candidate.package_id,
+					&candidate.version,
+					&candidate.provides,
+				)
+		});
+
+		if let Some(package) = planned_conflict {
+			conflict.get_or_insert(format!(
+				"{} {} conflicts with {} {}",
+				candidate.package_id, candidate.version, package.package_id, package.version
+			));
+			continue;
+		}
+
+		let installed_conflict = installed
+			.iter()
+			.find(|package| candidate.rejects(&package.package_id, &package.version, &[]));
+
+		if let Some(package) = installed_conflict {
+			conflict.get_or_insert(format!(
+				"{} {} conflicts with installed {} {}",
+				candidate.package_id, candidate.version, package.package_id, package.version
+			));
+			continue;
+		}
+
+		return Ok(candidate);
+	}
+
+	Err(conflict.unwrap_or_else(|| format!("No available version satisfies {}", dependency)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utility::parse_relations;
+
+	fn candidate(
+		package_id: &str,
+		version: &str,
+		quality: i32,
+		fields: &[(&str, &str)],
+	) -> Candidate {
+		let field = |name: &str| {
+			fields
+				.iter()
+				.find(|(field, _)| *field == name)
+				.map(|(_, value)| parse_relations(value))
+				.unwrap_or_default()
+		};
+
+		Candidate {
+			id: format!("{}-{}", package_id, version),
+			package_id: package_id.to_string(),
+			version: version.to_string(),
+			architecture: "iphoneos-arm64".to_string(),
+			repository_id: "chariz".to_string(),
+			quality,
+			package_filename: format!("./debs/{}.deb", package_id),
+			package_size: 100,
+			sha256_hash: None,
+			download_url: String::new(),
+			depends: field("depends"),
+			pre_depends: field("pre_depends"),
+			conflicts: field("conflicts"),
+			breaks: field("breaks"),
+			provides: field("provides"),
+		}
+	}
+
+	fn ids(plan: &[&Candidate]) -> Vec<String> {
+		plan.iter()
+			.map(|package| format!("{}={}", package.package_id, package.version))
+			.collect()
+	}
+
+	#[test]
+	fn resolves_transitive_dependencies() {
+		let candidates = vec![
+			candidate(
+				"tweak",
+				"1.0",
+				1,
+				&[("depends", "ws.hbang.common (>= 1.17), mobilesubstrate")],
+			),
+			candidate("ws.hbang.common", "1.16", 1, &[]),
+			candidate(
+				"ws.hbang.common",
+				"2.0~beta1",
+				2,
+				&[("depends", "firmware (>= 14.0)")],
+			),
+			candidate("ws.hbang.common", "1.17.1", 1, &[]),
+			candidate(
+				"ellekit",
+				"1.0",
+				1,
+				&[("provides", "mobilesubstrate (= 0.9.7000)")],
+			),
+		];
+
+		let installed = vec![Installed {
+			package_id: "firmware".to_string(),
+			version: "14.5".to_string(),
+		}];
+
+		let plan = resolve(parse_relations("tweak"), &installed, &candidates).unwrap();
+		assert_eq!(
+			ids(&plan),
+			vec!["tweak=1.0", "ws.hbang.common=2.0~beta1", "ellekit=1.0"]
+		);
+	}
+
+	#[test]
+	fn explains_unsatisfiable_relations() {
+		let candidates = vec![
+			candidate(
+				"tweak",
+				"1.0",
+				1,
+				&[("depends", "ws.hbang.common (>= 3.0), firmware")],
+			),
+			candidate("ws.hbang.common", "2.0", 1, &[]),
+		];
+
+		let errors = resolve(parse_relations("tweak"), &[], &candidates).unwrap_err();
+		assert_eq!(errors.len(), 2);
+		assert_eq!(errors[0].required_by.as_deref(), Some("tweak"));
+		assert_eq!(errors[0].relation, "ws.hbang.common (>= 3.0)");
+		assert_eq!(
+			errors[0].reasons,
+			vec!["No available version satisfies ws.hbang.common (>= 3.0)"]
+		);
+		assert_eq!(
+			errors[1].reasons,
+			vec!["firmware is not available from the enabled repositories"]
+		);
+	}
+
+	#[test]
+	fn reports_conflicting_packages() {
+		let candidates = vec![
+			candidate(
+				"substitute",
+				"2.0",
+				1,
+				&[("provides", "mobilesubstrate"), ("conflicts", "ellekit")],
+			),
+			candidate("ellekit", "1.0", 1, &[("provides", "mobilesubstrate")]),
+			candidate("tweak", "1.0", 1, &[("depends", "substitute | ellekit")]),
+		];
+
+		let plan = resolve(parse_relations("ellekit, tweak"), &[], &candidates).unwrap();
+		assert_eq!(ids(&plan), vec!["ellekit=1.0", "tweak=1.0"]);
+
+		let errors = resolve(parse_relations("ellekit, substitute"), &[], &candidates).unwrap_err();
+		assert_eq!(
+			errors[0].reasons,
+			vec!["substitute 2.0 conflicts with ellekit 1.0"]
+		);
+	}
+}
@@ -1,82 +1,128 @@
 use axum::{http::StatusCode, Json};
 use chrono::Utc;
+pub use models::PageRefs;
+use models::{DataResponse, ErrorResponse, Facets, ListResponse};
 use serde::Serialize;
-use serde_json::{json, Value};
+use serde_json::{to_value, Value};
+use tracing::error;
 
 type Response = (StatusCode, Json<Value>);
 
-pub fn data<T: Serialize>(status_code: StatusCode, body: T) -> Response {
-	let response_status = format!(
+fn response_status(status_code: StatusCode) -> String {
+	format!(
 		"{} {}",
 		status_code.as_u16(),
 		// If there is no status reason, don't show it
 		status_code.canonical_reason().unwrap_or("")
-	);
+	)
+}
 
-	let body = json!({
-		"status": response_status,
-		"date": Utc::now().to_rfc3339(),
-		"data": body
-	});
+fn response_date() -> String {
+	Utc::now().to_rfc3339()
+}
 
-	(status_code, Json(body))
+fn respond<T: Serialize>(status_code: StatusCode, body: T) -> Response {
+	match to_value(body) {
+		Ok(body) => (status_code, Json(body)),
+		Err(e) => {
+			error!(target: "http", "Failed to serialize response: {}", e);
+			error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to serialize response",
+			)
+		}
+	}
 }
 
-pub fn data_with_count<T: Serialize>(status_code: StatusCode, body: T, count: usize) -> Response {
-	let response_status = format!(
-		"{} {}",
-		status_code.as_u16(),
-		// If there is no status reason, don't show it
-		status_code.canonical_reason().unwrap_or("")
-	);
+fn list<T: Serialize>(status_code: StatusCode, body: T, count: usize) -> ListResponse<T> {
+	ListResponse {
+		status: response_status(status_code),
+		date: response_date(),
+		refs: None,
+		facets: None,
+		count,
+		total: None,
+		hasMore: None,
+		data: body,
+	}
+}
 
-	let body = json!({
-		"status": response_status,
-		"date": Utc::now().to_rfc3339(),
-		"count": count,
-		"data": body
-	});
+pub fn data<T: Serialize>(status_code: StatusCode, body: T) -> Response {
+	respond(
+		status_code,
+		DataResponse {
+			status: response_status(status_code),
+			date: response_date(),
+			data: body,
+		},
+	)
+}
 
-	(status_code, Json(body))
+pub fn data_with_count<T: Serialize>(status_code: StatusCode, body: T, count: usize) -> Response {
+	respond(status_code, list(status_code, body, count))
 }
 
-pub fn data_with_count_and_refs<T: Serialize, R: Serialize>(
+pub fn data_with_count_and_refs<T: Serialize>(
 	status_code: StatusCode,
 	body: T,
 	count: usize,
-	refs: R,
+	refs: PageRefs,
 ) -> Response {
-	let response_status = format!(
-		"{} {}",
-		status_code.as_u16(),
-		// If there is no status reason, don't show it
-		status_code.canonical_reason().unwrap_or("")
-	);
+	respond(
+		status_code,
+		ListResponse {
+			refs: Some(refs),
+			..list(status_code, body, count)
+		},
+	)
+}
 
-	let body = json!({
-		"status": response_status,
-		"date": Utc::now().to_rfc3339(),
-		"refs": refs,
-		"count": count,
-		"data": body
-	});
+pub fn data_with_total_and_refs<T: Serialize>(
+	status_code: StatusCode,
+	body: T,
+	count: usize,
+	total: i64,
+	has_more: bool,
+	refs: PageRefs,
+) -> Response {
+	respond(
+		status_code,
+		ListResponse {
+			refs: Some(refs),
+			total: Some(total),
+			hasMore: Some(has_more),
+			..list(status_code, body, count)
+		},
+	)
+}
 
-	(status_code, Json(body))
+pub fn data_with_total_refs_and_facets<T: Serialize>(
+	status_code: StatusCode,
+	body: T,
+	count: usize,
+	total: i64,
+	has_more: bool,
+	refs: PageRefs,
+	facets: Facets,
+) -> Response {
+	respond(
+		status_code,
+		ListResponse {
+			refs: Some(refs),
+			facets: Some(facets),
+			total: Some(total),
+			hasMore: Some(has_more),
+			..list(status_code, body, count)
+		},
+	)
 }
 
 pub fn error<T: Serialize>(status_code: StatusCode, body: T) -> Response {
-	let response_status = format!(
-		"{} {}",
-		status_code.as_u16(),
-		// If there is no status reason, don't show it
-		status_code.canonical_reason().unwrap_or("")
-	);
-
-	let body = json!({
-		"status": response_status,
-		"date": Utc::now().to_rfc3339(),
-		"error": body
-	});
+	let body = ErrorResponse {
+		status: response_status(status_code),
+		date: response_date(),
+		error: body,
+	};
 
-	(status_code, Json(body))
+	(status_code, Json(to_value(body).unwrap_or_default()))
 }
@@ -0,0 +1,184 @@
+use crate::utility::{sign_token, verify_token};
+use deadpool_postgres::tokio_postgres::Row;
+use serde::{Deserialize, Serialize};
+
+/// Which side of the cursor position a page is read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+	#[serde(rename = "n")]
+	Next,
+	#[serde(rename = "p")]
+	Previous,
+}
+
+/// Position in a ranked search, encoding the `(rank, quality, id)` of a row
+/// Results are ordered by rank descending, quality ascending and then ID
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+	#[serde(rename = "d")]
+	pub direction: Direction,
+	#[serde(rename = "r")]
+	pub rank: f64,
+	#[serde(rename = "q")]
+	pub quality: i32,
+	#[serde(rename = "i")]
+	pub id: String,
+}
+
+impl Cursor {
+	/// Builds a cursor pointing at a ranked row
+	/// Rows without a rank, such as ones ranked by Typesense, have no position
+	pub fn from_row(row: &Row, direction: Direction) -> Option<Self> {
+		// Exact ranks are `real` while the blended ones are `double precision`
+		let rank = match row.try_get::<_, Option<f64>>("rank") {
+			Ok(rank) => rank,
+			Err(_) => row
+				.try_get::<_, Option<f32>>("rank")
+				.ok()
+				.flatten()
+				.map(f64::from),
+		};
+
+		Some(Cursor {
+			direction,
+			rank: rank?,
+			quality: row.try_get("quality").ok()?,
+			id: row.try_get("id").ok()?,
+		})
+	}
+
+	/// Serializes the cursor into an opaque token signed with the key
+	pub fn encode(&self, key: &[u8]) -> Option<String> {
+		let payload = serde_json::to_vec(self).ok()?;
+		sign_token(key, &payload).ok()
+	}
+
+	/// Reads a token issued by `encode`, rejecting ones that were altered
+	pub fn decode(token: &str, key: &[u8]) -> Option<Self> {
+		let payload = verify_token(key, token)?;
+		serde_json::from_slice(&payload).ok()
+	}
+}
+
+/// Signed cursors for the pages before and after a page of ranked rows
+/// `cursor` is the one the page was read with, if any
+pub fn page_cursors(
+	key: &[u8],
+	rows: &[Row],
+	cursor: Option<&Cursor>,
+	page: u8,
+	has_more: bool,
+) -> (Option<String>, Option<String>) {
+	let (has_previous, has_next) = match cursor.map(|cursor| cursor.direction) {
+		Some(Direction::Next) => (true, has_more),
+		Some(Direction::Previous) => (has_more, true),
+		None => (page > 1, has_more),
+	};
+
+	let prev_cursor = match has_previous {
+		true => rows
+			.first()
+			.and_then(|row| Cursor::from_row(row, Direction::Previous)),
+		false => None,
+	};
+
+	let next_cursor = match has_next {
+		true => rows
+			.last()
+			.and_then(|row| Cursor::from_row(row, Direction::Next)),
+		false => None,
+	};
+
+	(
+		prev_cursor.and_then(|cursor| cursor.encode(key)),
+		next_cursor.and_then(|cursor| cursor.encode(key)),
+	)
+}
+
+/// SQL fragments to read a page relative to a cursor
+pub struct KeysetSql {
+	pub condition: String,
+	pub order: String,
+}
+
+/// Builds the keyset condition and ordering for the given columns
+/// The cursor's rank, quality and ID are bound at `first_param` onwards
+/// Previous pages are read in reverse and must be flipped by the caller
+pub fn keyset_sql(
+	direction: Direction,
+	rank: &str,
+	quality: &str,
+	id: &str,
+	first_param: usize,
+) -> KeysetSql {
+	let (comparison, order) = match direction {
+		Direction::Next => (">", "rank DESC, {quality} ASC, {id} ASC"),
+		Direction::Previous => ("<", "rank ASC, {quality} DESC, {id} DESC"),
+	};
+
+	// Negating the rank lets a single row comparison cover the mixed ordering
+	KeysetSql {
+		condition: format!(
+			"(-({rank})::float8, {quality}, {id}) {comparison} (-${}::float8, ${}::integer, ${}::text)",
+			first_param,
+			first_param + 1,
+			first_param + 2
+		),
+		order: order.replace("{quality}", quality).replace("{id}", id),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keyset_sql_binds_cursor_params() {
+		let sql = keyset_sql(
+			Direction::Next,
+			"ts_rank(x)",
+			"package.quality",
+			"package.id",
+			4,
+		);
+
+		assert_eq!(
+			sql.condition,
+			"(-(ts_rank(x))::float8, package.quality, package.id) > (-$4::float8, $5::integer, $6::text)"
+		);
+		assert_eq!(sql.order, "rank DESC, package.quality ASC, package.id ASC");
+
+		let sql = keyset_sql(Direction::Previous, "r", "quality", "id", 2);
+		assert!(sql.condition.contains(" < "));
+		assert_eq!(sql.order, "rank ASC, quality DESC, id DESC");
+	}
+
+	#[test]
+	fn cursors_serialize_compactly() {
+		let cursor = Cursor {
+			direction: Direction::Previous,
+			rank: 0.5,
+			quality: 1,
+			id: "filza".to_string(),
+		};
+
+		assert_eq!(
+			serde_json::to_string(&cursor).unwrap(),
+			"{\"d\":\"p\",\"r\":0.5,\"q\":1,\"i\":\"filza\"}"
+		);
+	}
+
+	#[test]
+	fn cursor_ranks_round_trip_exactly() {
+		// Keyset comparisons need the exact rank that Postgres produced
+		let cursor = Cursor {
+			direction: Direction::Next,
+			rank: 0.22797265648841858,
+			quality: 0,
+			id: "havoc".to_string(),
+		};
+
+		let json = serde_json::to_string(&cursor).unwrap();
+		assert_eq!(serde_json::from_str::<Cursor>(&json).unwrap(), cursor);
+	}
+}
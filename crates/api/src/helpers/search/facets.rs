@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Error, Result};
+pub use models::{Facets, MAX_FACET_VALUES};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceFilter {
+	Free,
+	Paid,
+}
+
+impl FromStr for PriceFilter {
+	type Err = Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value.to_ascii_lowercase().as_str() {
+			"free" => Ok(PriceFilter::Free),
+			"paid" => Ok(PriceFilter::Paid),
+			_ => Err(anyhow!("Unknown price filter: {}", value)),
+		}
+	}
+}
+
+/// Restricts package search results, values within a dimension are OR'd
+/// An empty list means the dimension is not filtered
+#[derive(Debug, Default)]
+pub struct PackageFilters {
+	pub sections: Vec<String>,
+	pub architectures: Vec<String>,
+	pub price: Option<PriceFilter>,
+	pub repositories: Vec<String>,
+	pub authors: Vec<String>,
+	pub tags: Vec<String>,
+	/// Architectures the requesting client is able to install
+	pub installable: Vec<String>,
+}
+
+/// Splits a comma-separated query parameter into its non-empty values
+pub fn split_list(value: &Option<String>) -> Vec<String> {
+	match value {
+		Some(value) => value
+			.split(',')
+			.map(|item| item.trim().to_string())
+			.filter(|item| !item.is_empty())
+			.collect(),
+		None => Vec::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use models::FacetCount;
+
+	#[test]
+	fn split_list_drops_empty_values() {
+		let value = Some("chariz, havoc,,".to_string());
+		assert_eq!(split_list(&value), vec!["chariz", "havoc"]);
+		assert!(split_list(&None).is_empty());
+	}
+
+	#[test]
+	fn facets_merge_and_sort() {
+		let mut facets = Facets::default();
+		facets.add("price", "free".to_string(), 2);
+		facets.add("price", "paid".to_string(), 3);
+		facets.add("price", "paid".to_string(), 1);
+		facets.add("unknown", "ignored".to_string(), 1);
+
+		let facets = facets.finish();
+		assert_eq!(
+			facets.price,
+			vec![
+				FacetCount {
+					value: "paid".to_string(),
+					count: 4
+				},
+				FacetCount {
+					value: "free".to_string(),
+					count: 2
+				},
+			]
+		);
+	}
+}
@@ -0,0 +1,161 @@
+use crate::AppState;
+use anyhow::{anyhow, Error, Result};
+use deadpool_postgres::{tokio_postgres::Row, Pool};
+use std::{fmt, str::FromStr};
+
+mod cursor;
+mod facets;
+mod postgres;
+mod typesense;
+
+pub use self::cursor::*;
+pub use self::facets::*;
+pub use self::postgres::*;
+pub use self::typesense::*;
+
+/// Backends that are able to serve package search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchEngine {
+	Postgres,
+	Typesense,
+}
+
+impl FromStr for SearchEngine {
+	type Err = Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value.to_ascii_lowercase().as_str() {
+			"postgres" => Ok(SearchEngine::Postgres),
+			"typesense" => Ok(SearchEngine::Typesense),
+			_ => Err(anyhow!("Unknown search engine: {}", value)),
+		}
+	}
+}
+
+impl fmt::Display for SearchEngine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SearchEngine::Postgres => write!(f, "postgres"),
+			SearchEngine::Typesense => write!(f, "typesense"),
+		}
+	}
+}
+
+/// How strictly a search query is matched against the index
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchMode {
+	/// Whole words only, the historical behaviour
+	#[default]
+	Exact,
+	/// Every word may be the start of an indexed word
+	Prefix,
+	/// Prefix matching plus trigram similarity for typos
+	Fuzzy,
+}
+
+impl FromStr for SearchMode {
+	type Err = Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value.to_ascii_lowercase().as_str() {
+			"exact" => Ok(SearchMode::Exact),
+			"prefix" => Ok(SearchMode::Prefix),
+			"fuzzy" => Ok(SearchMode::Fuzzy),
+			_ => Err(anyhow!("Unknown search mode: {}", value)),
+		}
+	}
+}
+
+/// Parameters shared by every package search backend
+pub struct PackageQuery<'a> {
+	pub q: &'a str,
+	pub limit: u8,
+	pub page: u8,
+	pub mode: SearchMode,
+	pub filters: PackageFilters,
+	/// Position to continue from, takes precedence over `page`
+	pub cursor: Option<Cursor>,
+}
+
+/// A page of ranked package rows with facet counts for the whole result set
+pub struct SearchResults {
+	pub rows: Vec<Row>,
+	pub facets: Facets,
+	/// Number of rows matching the query across every page
+	pub total: i64,
+	/// Whether more rows exist past this page in the direction it was read
+	pub has_more: bool,
+}
+
+impl PackageQuery<'_> {
+	/// Number of rows to skip for the requested page
+	pub fn offset(&self) -> i64 {
+		(i64::from(self.page) - 1) * i64::from(self.limit)
+	}
+}
+
+/// Converts a free-form query into a `to_tsquery` expression of prefix terms
+/// Returns None when nothing searchable is left after sanitizing
+pub fn prefix_tsquery(q: &str) -> Option<String> {
+	let terms = q
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|term| !term.is_empty())
+		.map(|term| format!("{}:*", term.to_lowercase()))
+		.collect::<Vec<String>>();
+
+	match terms.is_empty() {
+		true => None,
+		false => Some(terms.join(" & ")),
+	}
+}
+
+/// A backend which resolves a package search into ranked package rows
+/// Every backend returns the same columns so handlers can stay agnostic
+/// Rows are always loaded from the given pool, even when ranked elsewhere
+// Only called on concrete backends, whose futures are known to be `Send`
+#[allow(async_fn_in_trait)]
+pub trait PackageSearch {
+	async fn search_packages(&self, pool: &Pool, query: &PackageQuery<'_>)
+		-> Result<SearchResults>;
+}
+
+/// Runs a package search against the given engine
+pub async fn search_packages(
+	state: &AppState,
+	engine: SearchEngine,
+	query: &PackageQuery<'_>,
+) -> Result<SearchResults> {
+	match engine {
+		SearchEngine::Postgres => Postgres.search_packages(&state.pool, query).await,
+		SearchEngine::Typesense => state.typesense.search_packages(&state.pool, query).await,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn prefix_tsquery_builds_prefix_terms() {
+		assert_eq!(prefix_tsquery("cyd"), Some("cyd:*".to_string()));
+		assert_eq!(
+			prefix_tsquery("Filza File"),
+			Some("filza:* & file:*".to_string())
+		);
+	}
+
+	#[test]
+	fn prefix_tsquery_strips_operators() {
+		assert_eq!(
+			prefix_tsquery("a|b & !c:*"),
+			Some("a:* & b:* & c:*".to_string())
+		);
+		assert_eq!(prefix_tsquery("'):*"), None);
+	}
+
+	#[test]
+	fn search_mode_parses_case_insensitively() {
+		assert_eq!("Fuzzy".parse::<SearchMode>().unwrap(), SearchMode::Fuzzy);
+		assert!("typo".parse::<SearchMode>().is_err());
+	}
+}
@@ -0,0 +1,320 @@
+use super::{
+	keyset_sql, prefix_tsquery, Direction, Facets, PackageFilters, PackageQuery, PackageSearch,
+	PriceFilter, SearchMode, SearchResults, MAX_FACET_VALUES,
+};
+use crate::helpers::pg_client;
+use anyhow::Result;
+use deadpool_postgres::{
+	tokio_postgres::{
+		types::{ToSql, Type},
+		Row,
+	},
+	Pool,
+};
+
+/// SQL fragments used to match and rank rows for a search mode
+pub struct SearchSql {
+	pub condition: String,
+	pub rank: String,
+	pub tsquery: Option<String>,
+}
+
+/// Builds the match condition and rank expression for `table.search_vector`
+/// `$1` must be the raw query, the prefix tsquery is bound at `tsquery_param`
+/// `fuzzy_column` is compared with `pg_trgm` similarity in fuzzy mode
+pub fn search_sql(
+	mode: SearchMode,
+	q: &str,
+	table: &str,
+	fuzzy_column: &str,
+	tsquery_param: usize,
+) -> SearchSql {
+	let exact_rank = format!("ts_rank({table}.search_vector, plainto_tsquery('simple', $1))");
+	let tsquery = match mode {
+		SearchMode::Exact => None,
+		SearchMode::Prefix | SearchMode::Fuzzy => prefix_tsquery(q),
+	};
+
+	// Without any prefix terms there is nothing more lenient to run
+	let prefix = match &tsquery {
+		Some(_) => format!("{table}.search_vector @@ to_tsquery('simple', ${tsquery_param})"),
+		None => {
+			return SearchSql {
+				condition: format!("{table}.search_vector @@ plainto_tsquery('simple', $1)"),
+				rank: exact_rank,
+				tsquery,
+			}
+		}
+	};
+
+	// Whole-word matches are weighted double so exact results still win
+	let prefix_rank = format!(
+		"{exact_rank} * 2 + ts_rank({table}.search_vector, to_tsquery('simple', ${tsquery_param}))"
+	);
+
+	match mode {
+		SearchMode::Fuzzy => SearchSql {
+			condition: format!("({prefix} OR {fuzzy_column} % $1)"),
+			rank: format!("{prefix_rank} + similarity({fuzzy_column}, $1)"),
+			tsquery,
+		},
+		_ => SearchSql {
+			condition: prefix,
+			rank: prefix_rank,
+			tsquery,
+		},
+	}
+}
+
+/// Full-text search backed by the `search_vector` column in Postgres
+pub struct Postgres;
+
+/// Whether a price column counts as free, shared by the filter and its facet
+/// so they agree on packages without a price, which count as paid
+fn is_free(column: &str) -> String {
+	format!("coalesce(lower({}), '') = 'free'", column)
+}
+
+/// Builds SQL conditions for the filters, binding their values onto `params`
+fn filter_conditions<'a>(
+	filters: &'a PackageFilters,
+	params: &mut Vec<&'a (dyn ToSql + Sync)>,
+) -> Vec<String> {
+	let mut conditions = Vec::new();
+
+	for (column, values) in [
+		("package.section", &filters.sections),
+		("package.architecture", &filters.architectures),
+		("package.repository_id", &filters.repositories),
+		("package.author", &filters.authors),
+		("package.architecture", &filters.installable),
+	] {
+		if !values.is_empty() {
+			params.push(values);
+			conditions.push(format!("{} = ANY(${})", column, params.len()));
+		}
+	}
+
+	if !filters.tags.is_empty() {
+		params.push(&filters.tags);
+		conditions.push(format!("package.tags && ${}", params.len()));
+	}
+
+	match filters.price {
+		Some(PriceFilter::Free) => conditions.push(is_free("package.price")),
+		Some(PriceFilter::Paid) => conditions.push(format!("NOT {}", is_free("package.price"))),
+		None => (),
+	}
+
+	conditions
+}
+
+impl PackageSearch for Postgres {
+	async fn search_packages(
+		&self,
+		pool: &Pool,
+		query: &PackageQuery<'_>,
+	) -> Result<SearchResults> {
+		let sql = search_sql(
+			query.mode,
+			query.q,
+			"package",
+			"coalesce(package.name, package.package_id)",
+			2,
+		);
+
+		// The facet query shares every parameter except the pagination ones
+		let mut params: Vec<&(dyn ToSql + Sync)> = vec![&query.q];
+		if let Some(tsquery) = &sql.tsquery {
+			params.push(tsquery);
+		}
+
+		let mut conditions = vec![sql.condition];
+		conditions.extend(filter_conditions(&query.filters, &mut params));
+		let condition = conditions.join(" AND ");
+
+		let mut page_params = params.clone();
+		let direction = match &query.cursor {
+			Some(cursor) => cursor.direction,
+			None => Direction::Next,
+		};
+
+		let keyset = keyset_sql(
+			direction,
+			&sql.rank,
+			"package.quality",
+			"package.id",
+			params.len() + 1,
+		);
+
+		let mut page_condition = condition.clone();
+		if let Some(cursor) = &query.cursor {
+			page_params.push(&cursor.rank);
+			page_params.push(&cursor.quality);
+			page_params.push(&cursor.id);
+			page_condition = format!("{} AND {}", page_condition, keyset.condition);
+		}
+
+		// One extra row is read to tell whether another page follows
+		let limit = i64::from(query.limit) + 1;
+		let offset = match &query.cursor {
+			Some(_) => 0,
+			None => query.offset(),
+		};
+
+		page_params.push(&limit);
+		page_params.push(&offset);
+
+		// Support our legacy fields
+		let page_query = format!(
+			"
+			SELECT
+				package.*,
+				package.package_id AS package,
+				package.quality AS repositoryTier,
+				package.sileo_depiction AS sileoDepiction,
+				{} AS rank,
+				(to_jsonb(repository) || jsonb_build_object(
+					'slug', repository.id,
+					'tier', repository.quality,
+					'isBootstrap', repository.bootstrap
+				)) AS repository
+			FROM package
+			RIGHT JOIN
+				repository ON repository.id = package.repository_id
+			WHERE
+				package.visible = true
+				AND latest_version = true
+				AND {}
+			ORDER BY {}
+			LIMIT ${} OFFSET ${}
+		",
+			sql.rank,
+			page_condition,
+			keyset.order,
+			page_params.len() - 1,
+			page_params.len()
+		);
+
+		let facet_query = format!(
+			"
+			WITH matches AS (
+				SELECT
+					package.section,
+					package.architecture,
+					package.price,
+					package.repository_id,
+					package.author,
+					package.tags
+				FROM package
+				WHERE
+					package.visible = true
+					AND latest_version = true
+					AND {}
+			), counts AS (
+				SELECT 'section' AS facet, section AS value, count(*) AS count
+				FROM matches WHERE section IS NOT NULL GROUP BY section
+				UNION ALL
+				SELECT 'architecture', architecture, count(*)
+				FROM matches GROUP BY architecture
+				UNION ALL
+				SELECT 'price', CASE WHEN {} THEN 'free' ELSE 'paid' END, count(*)
+				FROM matches GROUP BY 2
+				UNION ALL
+				SELECT 'repository', repository_id, count(*)
+				FROM matches GROUP BY repository_id
+				UNION ALL
+				SELECT 'author', author, count(*)
+				FROM matches WHERE author IS NOT NULL GROUP BY author
+				UNION ALL
+				SELECT 'tags', tag, count(*)
+				FROM matches, unnest(tags) AS tag GROUP BY tag
+				UNION ALL
+				SELECT 'total', 'total', count(*)
+				FROM matches
+			)
+			SELECT facet, value, count FROM (
+				SELECT *, row_number() OVER (
+					PARTITION BY facet ORDER BY count DESC, value
+				) AS position
+				FROM counts
+			) AS ranked
+			WHERE position <= {}
+		",
+			condition,
+			is_free("price"),
+			MAX_FACET_VALUES
+		);
+
+		let pg_client = pg_client(pool).await?;
+		let (mut rows, facet_rows) =
+			tokio::try_join!(pg_client.query(&page_query, &page_params), async {
+				// Prefix mode never references the raw query when counting
+				// so its type has to be given up front
+				let statement = pg_client.prepare_typed(&facet_query, &[Type::TEXT]).await?;
+				pg_client.query(&statement, &params).await
+			})?;
+
+		// Matches are already counted for the facets, so the total comes for free
+		let mut facets = Facets::default();
+		let mut total = 0;
+		for row in facet_rows {
+			match row.get::<_, &str>("facet") {
+				"total" => total = row.get("count"),
+				facet => facets.add(facet, row.get("value"), row.get("count")),
+			}
+		}
+
+		let has_more = rows.len() > usize::from(query.limit);
+		rows.truncate(usize::from(query.limit));
+		if direction == Direction::Previous {
+			rows.reverse();
+		}
+
+		Ok(SearchResults {
+			rows,
+			facets: facets.finish(),
+			total,
+			has_more,
+		})
+	}
+}
+
+/// Loads package rows by database ID, preserving the order of the given IDs
+/// Used by external search engines which only return matching IDs
+pub async fn hydrate_packages(pool: &Pool, ids: &[String]) -> Result<Vec<Row>> {
+	if ids.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let pg_client = pg_client(pool).await?;
+	let rows = pg_client
+		// Support our legacy fields
+		.query(
+			"
+			SELECT
+				package.*,
+				package.package_id AS package,
+				package.quality AS repositoryTier,
+				package.sileo_depiction AS sileoDepiction,
+				NULL::real AS rank,
+				(to_jsonb(repository) || jsonb_build_object(
+					'slug', repository.id,
+					'tier', repository.quality,
+					'isBootstrap', repository.bootstrap
+				)) AS repository
+			FROM package
+			RIGHT JOIN
+				repository ON repository.id = package.repository_id
+			WHERE
+				package.visible = true
+				AND package.id = ANY($1)
+			ORDER BY
+				array_position($1, package.id)
+		",
+			&[&ids],
+		)
+		.await?;
+
+	Ok(rows)
+}
@@ -0,0 +1,282 @@
+use super::{
+	hydrate_packages, Facets, PackageFilters, PackageQuery, PackageSearch, PriceFilter, SearchMode,
+	SearchResults, MAX_FACET_VALUES,
+};
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Collection the indexer writes package documents into
+const COLLECTION: &str = "packages";
+
+/// Fields searched in order of importance
+const QUERY_BY: &str = "name,package_id,author,description";
+
+/// Fields the indexer declares with `facet: true`
+/// Prices are faceted on the indexer's boolean `is_paid` field, since raw prices
+/// have too many distinct values to be counted within `max_facet_values`
+const FACET_BY: &str = "section,architecture,is_paid,repository_id,author,tags";
+
+#[derive(Deserialize)]
+struct SearchResponse {
+	#[serde(default)]
+	found: u64,
+	hits: Vec<SearchHit>,
+	#[serde(default)]
+	facet_counts: Vec<FacetField>,
+}
+
+#[derive(Deserialize)]
+struct FacetField {
+	field_name: String,
+	counts: Vec<FacetValue>,
+}
+
+#[derive(Deserialize)]
+struct FacetValue {
+	value: String,
+	count: i64,
+}
+
+#[derive(Deserialize)]
+struct SearchHit {
+	document: SearchDocument,
+}
+
+#[derive(Deserialize)]
+struct SearchDocument {
+	id: String,
+}
+
+/// Typo-tolerant prefix search backed by Typesense
+/// Typesense only ranks the matches, rows are loaded from Postgres
+pub struct Typesense {
+	url: String,
+	api_key: String,
+	http: Client,
+}
+
+impl Typesense {
+	pub fn new(url: &str, api_key: &str) -> Result<Self> {
+		let http = Client::builder().timeout(Duration::from_secs(5)).build()?;
+
+		Ok(Typesense {
+			url: url.trim_end_matches('/').to_string(),
+			api_key: api_key.to_string(),
+			http,
+		})
+	}
+
+	/// Fails unless Typesense reports itself as healthy
+	pub async fn health(&self) -> Result<()> {
+		self.http
+			.get(format!("{}/health", self.url))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		Ok(())
+	}
+
+	/// Returns the matching package database IDs in ranked order
+	/// Facet counts are computed by Typesense over the whole result set
+	/// Also reports the total number of matches across every page
+	pub async fn search_ids(&self, query: &PackageQuery<'_>) -> Result<(Vec<String>, Facets, u64)> {
+		let (prefix, num_typos) = match query.mode {
+			SearchMode::Exact => ("false", "0"),
+			SearchMode::Prefix => ("true", "0"),
+			SearchMode::Fuzzy => ("true", "2"),
+		};
+
+		let response = self
+			.http
+			.get(format!(
+				"{}/collections/{}/documents/search",
+				self.url, COLLECTION
+			))
+			.header("X-TYPESENSE-API-KEY", &self.api_key)
+			.query(&[
+				("q", query.q),
+				("query_by", QUERY_BY),
+				("filter_by", &filter_by(&query.filters)),
+				("facet_by", FACET_BY),
+				("max_facet_values", &MAX_FACET_VALUES.to_string()),
+				("include_fields", "id"),
+				("prefix", prefix),
+				("num_typos", num_typos),
+				("per_page", &query.limit.to_string()),
+				("page", &query.page.to_string()),
+			])
+			.send()
+			.await?
+			.error_for_status()?
+			.json::<SearchResponse>()
+			.await?;
+
+		let mut facets = Facets::default();
+		for field in response.facet_counts {
+			let facet = match field.field_name.as_str() {
+				"repository_id" => "repository",
+				"is_paid" => "price",
+				name => name,
+			};
+
+			for value in field.counts {
+				let label = match (facet, value.value.as_str()) {
+					("price", "true") => "paid".to_string(),
+					("price", _) => "free".to_string(),
+					_ => value.value,
+				};
+
+				facets.add(facet, label, value.count);
+			}
+		}
+
+		let ids = response
+			.hits
+			.into_iter()
+			.map(|hit| hit.document.id)
+			.collect();
+
+		Ok((ids, facets.finish(), response.found))
+	}
+}
+
+impl PackageSearch for Typesense {
+	async fn search_packages(
+		&self,
+		pool: &Pool,
+		query: &PackageQuery<'_>,
+	) -> Result<SearchResults> {
+		let (ids, facets, found) = self.search_ids(query).await?;
+		let rows = hydrate_packages(pool, &ids).await?;
+		Ok(SearchResults {
+			rows,
+			facets,
+			total: found as i64,
+			has_more: found > u64::from(query.page) * u64::from(query.limit),
+		})
+	}
+}
+
+/// Builds a Typesense `filter_by` expression for the filters
+fn filter_by(filters: &PackageFilters) -> String {
+	let mut conditions = vec![
+		"visible:true".to_string(),
+		"latest_version:true".to_string(),
+	];
+
+	for (field, values) in [
+		("section", &filters.sections),
+		("architecture", &filters.architectures),
+		("repository_id", &filters.repositories),
+		("author", &filters.authors),
+		("tags", &filters.tags),
+		("architecture", &filters.installable),
+	] {
+		if !values.is_empty() {
+			// Backticks let values contain commas and other reserved characters
+			let values = values
+				.iter()
+				.map(|value| format!("`{}`", value.replace('`', "")))
+				.collect::<Vec<String>>()
+				.join(",");
+
+			conditions.push(format!("{}:=[{}]", field, values));
+		}
+	}
+
+	match filters.price {
+		Some(PriceFilter::Free) => conditions.push("is_paid:false".to_string()),
+		Some(PriceFilter::Paid) => conditions.push("is_paid:true".to_string()),
+		None => (),
+	}
+
+	conditions.join(" && ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{extract::Query, http::HeaderMap, routing::get, Json, Router};
+	use serde_json::{json, Value};
+	use std::{collections::HashMap, net::SocketAddr};
+
+	async fn fake_search(
+		headers: HeaderMap,
+		Query(params): Query<HashMap<String, String>>,
+	) -> Json<Value> {
+		assert_eq!(headers["X-TYPESENSE-API-KEY"], "typesense");
+		assert_eq!(params["q"], "filz");
+		assert_eq!(params["query_by"], QUERY_BY);
+		assert_eq!(params["prefix"], "true");
+		assert_eq!(params["num_typos"], "2");
+		assert_eq!(params["facet_by"], FACET_BY);
+		assert_eq!(params["per_page"], "10");
+		assert_eq!(params["page"], "2");
+		assert_eq!(
+			params["filter_by"],
+			"visible:true && latest_version:true && repository_id:=[`chariz`,`havoc`] && is_paid:true"
+		);
+
+		Json(json!({
+			"found": 2,
+			"hits": [
+				{ "document": { "id": "filza" }, "text_match": 100 },
+				{ "document": { "id": "filzaescaped" }, "text_match": 90 }
+			],
+			"facet_counts": [
+				{
+					"field_name": "is_paid",
+					"counts": [
+						{ "value": "true", "count": 2 },
+						{ "value": "false", "count": 1 }
+					]
+				},
+				{
+					"field_name": "repository_id",
+					"counts": [{ "value": "havoc", "count": 2 }]
+				}
+			]
+		}))
+	}
+
+	#[tokio::test]
+	async fn search_ids_preserves_ranked_order_and_facets() {
+		let app = Router::new().route(
+			&format!("/collections/{}/documents/search", COLLECTION),
+			get(fake_search),
+		);
+
+		let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+			.serve(app.into_make_service());
+		let addr = server.local_addr();
+		tokio::spawn(server);
+
+		let typesense = Typesense::new(&format!("http://{}/", addr), "typesense").unwrap();
+		let (ids, facets, found) = typesense
+			.search_ids(&PackageQuery {
+				q: "filz",
+				limit: 10,
+				page: 2,
+				mode: SearchMode::Fuzzy,
+				filters: PackageFilters {
+					repositories: vec!["chariz".to_string(), "havoc".to_string()],
+					price: Some(PriceFilter::Paid),
+					..Default::default()
+				},
+				cursor: None,
+			})
+			.await
+			.unwrap();
+
+		assert_eq!(ids, vec!["filza", "filzaescaped"]);
+		assert_eq!(found, 2);
+		assert_eq!(facets.price[0].value, "paid");
+		assert_eq!(facets.price[0].count, 2);
+		assert_eq!(facets.price[1].value, "free");
+		assert_eq!(facets.repository[0].value, "havoc");
+	}
+}
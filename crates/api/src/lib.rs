@@ -0,0 +1,104 @@
+use axum::{
+	extract::DefaultBodyLimit,
+	http::StatusCode,
+	middleware::from_fn_with_state,
+	routing::{get, post},
+	Json, Router,
+};
+use chrono::Utc;
+use serde_json::json;
+
+pub mod helpers;
+pub mod metrics;
+pub mod middleware;
+mod routes;
+pub mod server;
+mod state;
+pub mod types;
+pub mod utility;
+
+use self::middleware::{cors_middleware, trace_middleware};
+pub use self::state::*;
+
+/// Builds the router with every route of the API, sharing the given state
+pub fn build_router(state: AppState) -> Router {
+	Router::new()
+		.route("/metrics", get(routes::info::metrics))
+		.route("/v2/", get(routes::info::landing_page))
+		.route("/v2/livez", get(routes::info::livez))
+		.route("/v2/readyz", get(routes::info::readyz))
+		.route("/v2/healthz", get(routes::info::readyz))
+		.route("/v2/openapi.json", get(routes::info::openapi_json))
+		.route("/v2/openapi.yaml", get(routes::info::openapi_yaml))
+		.route(
+			"/v2/jailbreak/download/ingest",
+			post(routes::download::ingest)
+				.layer(DefaultBodyLimit::max(state.config.ingest_max_body_bytes)),
+		)
+		.route("/v2/jailbreak/package/search", get(routes::package::search))
+		.route(
+			"/v2/jailbreak/package/:package",
+			get(routes::package::lookup),
+		)
+		.route(
+			"/v2/jailbreak/package/resolve",
+			post(routes::package::resolve),
+		)
+		.route(
+			"/v2/jailbreak/package/updates",
+			post(routes::package::updates),
+		)
+		.route(
+			"/v2/jailbreak/package/:package/dependents",
+			get(routes::package::dependents),
+		)
+		.route(
+			"/v2/jailbreak/package/:package/versions",
+			get(routes::package::versions),
+		)
+		.route(
+			"/v2/jailbreak/package/multi",
+			get(routes::package::multi_lookup),
+		)
+		.route(
+			"/v2/jailbreak/repository/ranking",
+			get(routes::repository::ranking),
+		)
+		.route(
+			"/v2/jailbreak/repository/safety",
+			get(routes::repository::safety),
+		)
+		.route(
+			"/v2/jailbreak/repository/search",
+			get(routes::repository::search),
+		)
+		.route(
+			"/v2/jailbreak/repository/:repository",
+			get(routes::repository::lookup),
+		)
+		.route(
+			"/v2/jailbreak/repository/:repository/packages",
+			get(routes::repository::packages),
+		)
+		.route(
+			"/v2/jailbreak/repository/:repository/dists/*path",
+			get(routes::repository::dists),
+		)
+		.route(
+			"/v2/jailbreak/repository/:repository/*file",
+			get(routes::repository::file),
+		)
+		// Layers only wrap what's already registered, so the fallback goes first
+		.fallback(|| async {
+			(
+				StatusCode::NOT_FOUND,
+				Json(json!({
+					"status": "404 Not Found",
+					"date": Utc::now().to_rfc3339()
+				})),
+			)
+		})
+		.layer(from_fn_with_state(state.clone(), cors_middleware))
+		.layer(from_fn_with_state(state.clone(), trace_middleware))
+		.with_state(state)
+}
@@ -1,36 +1,77 @@
-use crate::{helpers::create_db, utility::load_runtime_config};
-use axum::{
-	http::{HeaderValue, Request, StatusCode},
-	middleware::{self, Next},
-	response::Response,
-	routing::{get, post},
-	Json, Router,
+use api::{
+	build_router,
+	server::{serve, shutdown_signal},
+	utility::{init_logging, load_runtime_config},
+	AppState,
 };
-use chrono::Utc;
 use sentry::{capture_message, init, integrations::anyhow::capture_anyhow, ClientOptions, Level};
-use serde_json::json;
-use std::{net::SocketAddr, process::exit, sync::OnceLock};
+use std::{env::args, path::PathBuf, process::exit};
+use tracing::{error, info};
 
-mod helpers;
-mod routes;
-mod types;
-mod utility;
+/// Flags accepted on the command line, everything else lives in the config
+#[derive(Default)]
+struct Args {
+	/// Config file to layer under the environment
+	config: Option<PathBuf>,
+	/// Print the effective config with its secrets redacted and exit
+	print_config: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+	let mut parsed = Args::default();
+	let mut args = args().skip(1);
 
-#[warn(clippy::all)]
-#[warn(clippy::correctness)]
-#[warn(clippy::suspicious)]
-#[warn(clippy::pedantic)]
-#[warn(clippy::style)]
-#[warn(clippy::complexity)]
-#[warn(clippy::perf)]
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--config" => match args.next() {
+				Some(path) => parsed.config = Some(PathBuf::from(path)),
+				None => return Err("--config requires a path".to_string()),
+			},
+			"--print-config" => parsed.print_config = true,
+			_ => return Err(format!("Unknown argument: {}", arg)),
+		}
+	}
 
-static POD_NAME: OnceLock<String> = OnceLock::new();
+	Ok(parsed)
+}
 
 /// Main entry point for the HTTP server
 /// All route handlers run in a tokio context
 #[tokio::main]
 async fn main() {
-	let config = load_runtime_config();
+	let args = match parse_args() {
+		Ok(args) => args,
+		Err(e) => {
+			eprintln!("{}", e);
+			eprintln!("Usage: api [--config <path>] [--print-config]");
+			exit(1);
+		}
+	};
+
+	let config = match load_runtime_config(args.config.as_deref()) {
+		Ok(config) => config,
+		Err(e) => {
+			eprintln!("FATAL: {:#}", e);
+			exit(1);
+		}
+	};
+
+	if args.print_config {
+		match serde_yaml::to_string(&config.redacted()) {
+			Ok(config) => print!("{}", config),
+			Err(e) => {
+				eprintln!("FATAL: Failed to serialize config: {}", e);
+				exit(1);
+			}
+		}
+
+		return;
+	}
+
+	if let Err(e) = init_logging(&config) {
+		eprintln!("FATAL: Failed to initialize logging: {}", e);
+		exit(1);
+	}
 
 	let _guard = init((
 		config.sentry_dsn.as_str(),
@@ -41,98 +82,30 @@ async fn main() {
 		},
 	));
 
-	if let Err(e) = create_db().await {
-		capture_anyhow(&e);
-		eprintln!("[indexer] failed to connect to postgres: {}", e);
-		exit(1);
-	}
-
-	let app = Router::new()
-		.route("/v2/", get(routes::info::landing_page))
-		.route("/v2/healthz", get(routes::info::health_check))
-		.route("/v2/openapi.json", get(routes::info::openapi_json))
-		.route("/v2/openapi.yaml", get(routes::info::openapi_yaml))
-		.route(
-			"/v2/jailbreak/download/ingest",
-			post(routes::download::ingest),
-		)
-		.route("/v2/jailbreak/package/search", get(routes::package::search))
-		.route(
-			"/v2/jailbreak/package/:package",
-			get(routes::package::lookup),
-		)
-		.route(
-			"/v2/jailbreak/package/multi",
-			get(routes::package::multi_lookup),
-		)
-		.route(
-			"/v2/jailbreak/repository/ranking",
-			get(routes::repository::ranking),
-		)
-		.route(
-			"/v2/jailbreak/repository/safety",
-			get(routes::repository::safety),
-		)
-		.route(
-			"/v2/jailbreak/repository/search",
-			get(routes::repository::search),
-		)
-		.route(
-			"/v2/jailbreak/repository/:repository",
-			get(routes::repository::lookup),
-		)
-		.route(
-			"/v2/jailbreak/repository/:repository/packages",
-			get(routes::repository::packages),
-		)
-		.layer(middleware::from_fn(cors_middleware))
-		.fallback(|| async {
-			(
-				StatusCode::NOT_FOUND,
-				Json(json!({
-					"status": "404 Not Found",
-					"date": Utc::now().to_rfc3339()
-				})),
-			)
-		});
-
-	// TODO: Error Handler?
-	let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-
-	println!("http: listening on {addr}");
-	axum::Server::bind(&addr)
-		.serve(app.into_make_service())
-		.await
-		.unwrap_or_else(|err| {
-			capture_message("failed to bind http port", Level::Fatal);
-			println!("panic: failed to bind http port - {err}");
+	let state = match AppState::new(config).await {
+		Ok(state) => state,
+		Err(e) => {
+			capture_anyhow(&e);
+			error!("Failed to initialize: {:#}", e);
 			exit(1);
-		});
-}
+		}
+	};
 
-async fn cors_middleware<B>(request: Request<B>, next: Next<B>) -> Response {
-	let mut response = next.run(request).await;
-	let headers = response.headers_mut();
+	let config = state.config.clone();
+	let pool = state.pool.clone();
+	let ingest = state.ingest.clone();
+	let app = build_router(state);
 
-	headers.insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
-	headers.insert(
-		"Access-Control-Allow-Methods",
-		HeaderValue::from_static("GET, POST, OPTIONS"),
-	);
-	headers.insert(
-		"Access-Control-Allow-Headers",
-		HeaderValue::from_static("Content-Type, *"),
-	);
+	if let Err(err) = serve(app, &config, shutdown_signal()).await {
+		capture_message("failed to serve http", Level::Fatal);
+		error!(target: "http", "Failed to serve http: {:#}", err);
+		exit(1);
+	}
 
-	// Also add the X-Served-By header and X-Request-ID (TODO)
-	let pod_name = POD_NAME.get_or_init(|| {
-		let pod_name = std::env::var("POD_NAME").unwrap_or_else(|_| "unknown".to_string());
-		pod_name
-	});
+	// Undelivered download events stay spooled for the next start
+	ingest.close().await;
 
-	headers.insert(
-		"X-Served-By",
-		HeaderValue::from_str(pod_name).unwrap_or(HeaderValue::from_static("unknown")),
-	);
-	response
+	// Connections still checked out are closed as they're returned
+	pool.close();
+	info!(target: "http", "Shut down");
 }
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use prometheus::{
+	exponential_buckets, Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+	IntGauge, Opts, Registry, TextEncoder,
+};
+use std::{
+	future::Future,
+	time::{Duration, Instant},
+};
+
+/// Content type of the Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Collectors for everything exported on `/metrics`
+/// Each state owns its registry, so routers built side by side don't share counts
+pub struct Metrics {
+	registry: Registry,
+	http_requests: IntCounterVec,
+	http_duration: HistogramVec,
+	query_duration: HistogramVec,
+	ingest_forwards: IntCounterVec,
+	ingest_depth: IntGauge,
+	ingest_dropped: IntCounter,
+	ingest_rejected: IntCounterVec,
+	pool_max_size: IntGauge,
+	pool_size: IntGauge,
+	pool_available: IntGauge,
+	pool_waiting: IntGauge,
+	safety_list_age: Gauge,
+}
+
+impl Metrics {
+	pub fn new() -> Result<Self> {
+		let registry = Registry::new_custom(Some("canister".to_string()), None)?;
+
+		let http_requests = IntCounterVec::new(
+			Opts::new("http_requests_total", "Requests handled per route"),
+			&["method", "route", "status"],
+		)?;
+
+		let http_duration = HistogramVec::new(
+			HistogramOpts::new(
+				"http_request_duration_seconds",
+				"Time taken to respond per route",
+			)
+			.buckets(exponential_buckets(0.001, 2.0, 14)?),
+			&["method", "route"],
+		)?;
+
+		let query_duration = HistogramVec::new(
+			HistogramOpts::new(
+				"db_query_duration_seconds",
+				"Time taken by database queries per handler",
+			)
+			.buckets(exponential_buckets(0.0005, 2.0, 14)?),
+			&["handler"],
+		)?;
+
+		let ingest_forwards = IntCounterVec::new(
+			Opts::new(
+				"ingest_forwards_total",
+				"Batches of download events delivered per sink by outcome",
+			),
+			&["sink", "result"],
+		)?;
+
+		let ingest_depth = IntGauge::new(
+			"ingest_queue_depth",
+			"Download events spooled and waiting for delivery",
+		)?;
+
+		let ingest_dropped = IntCounter::new(
+			"ingest_dropped_total",
+			"Download events dropped since the queue was full",
+		)?;
+
+		let ingest_rejected = IntCounterVec::new(
+			Opts::new(
+				"ingest_rejected_total",
+				"Download events rejected by validation and abuse protection",
+			),
+			&["reason"],
+		)?;
+
+		let pool_max_size = IntGauge::new("db_pool_max_size", "Most connections the pool opens")?;
+		let pool_size = IntGauge::new("db_pool_size", "Connections currently open")?;
+		let pool_available = IntGauge::new("db_pool_available", "Open connections that are idle")?;
+		let pool_waiting = IntGauge::new("db_pool_waiting", "Requests waiting for a connection")?;
+
+		let safety_list_age = Gauge::new(
+			"safety_list_age_seconds",
+			"Seconds since the piracy list was fetched, NaN until it is",
+		)?;
+
+		safety_list_age.set(f64::NAN);
+
+		registry.register(Box::new(http_requests.clone()))?;
+		registry.register(Box::new(http_duration.clone()))?;
+		registry.register(Box::new(query_duration.clone()))?;
+		registry.register(Box::new(ingest_forwards.clone()))?;
+		registry.register(Box::new(ingest_depth.clone()))?;
+		registry.register(Box::new(ingest_dropped.clone()))?;
+		registry.register(Box::new(ingest_rejected.clone()))?;
+		registry.register(Box::new(pool_max_size.clone()))?;
+		registry.register(Box::new(pool_size.clone()))?;
+		registry.register(Box::new(pool_available.clone()))?;
+		registry.register(Box::new(pool_waiting.clone()))?;
+		registry.register(Box::new(safety_list_age.clone()))?;
+
+		Ok(Metrics {
+			registry,
+			http_requests,
+			http_duration,
+			query_duration,
+			ingest_forwards,
+			ingest_depth,
+			ingest_dropped,
+			ingest_rejected,
+			pool_max_size,
+			pool_size,
+			pool_available,
+			pool_waiting,
+			safety_list_age,
+		})
+	}
+
+	/// Records a handled request under its route template
+	pub fn observe_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+		self.http_requests
+			.with_label_values(&[method, route, &status.to_string()])
+			.inc();
+
+		self.http_duration
+			.with_label_values(&[method, route])
+			.observe(latency.as_secs_f64());
+	}
+
+	/// Awaits a database query, recording how long it took for the handler
+	pub async fn time_query<F: Future>(&self, handler: &str, query: F) -> F::Output {
+		let started = Instant::now();
+		let result = query.await;
+
+		self.query_duration
+			.with_label_values(&[handler])
+			.observe(started.elapsed().as_secs_f64());
+
+		result
+	}
+
+	/// Counts an attempt to deliver a batch of download events to a sink
+	pub fn observe_ingest_forward(&self, sink: &str, success: bool) {
+		let result = match success {
+			true => "success",
+			false => "failure",
+		};
+
+		self.ingest_forwards
+			.with_label_values(&[sink, result])
+			.inc();
+	}
+
+	pub fn set_ingest_depth(&self, depth: usize) {
+		self.ingest_depth.set(depth as i64);
+	}
+
+	pub fn observe_ingest_drop(&self, events: usize) {
+		self.ingest_dropped.inc_by(events as u64);
+	}
+
+	/// Counts events rejected before they were queued, by why they were
+	pub fn observe_ingest_rejection(&self, reason: &str, events: usize) {
+		self.ingest_rejected
+			.with_label_values(&[reason])
+			.inc_by(events as u64);
+	}
+
+	/// Samples the gauges and renders every metric in the text format
+	pub fn render(&self, pool: &Pool, safety_list_fetched: Option<Instant>) -> Result<String> {
+		let status = pool.status();
+		self.pool_max_size.set(status.max_size as i64);
+		self.pool_size.set(status.size as i64);
+		self.pool_available.set(status.available as i64);
+		self.pool_waiting.set(status.waiting as i64);
+
+		if let Some(fetched) = safety_list_fetched {
+			self.safety_list_age.set(fetched.elapsed().as_secs_f64());
+		}
+
+		let mut buffer = Vec::new();
+		TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+		Ok(String::from_utf8(buffer)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use deadpool_postgres::{tokio_postgres::NoTls, Config, PoolConfig, Runtime};
+
+	#[tokio::test]
+	async fn renders_observed_metrics() {
+		let metrics = Metrics::new().unwrap();
+		metrics.observe_request("GET", "/v2/", 200, Duration::from_millis(3));
+		metrics.observe_ingest_forward("vector", false);
+		assert_eq!(metrics.time_query("package_lookup", async { 1 }).await, 1);
+
+		// Connections are only opened on first use, so nothing needs to listen
+		let pool = Config {
+			url: Some("postgres://localhost/canister".to_string()),
+			pool: Some(PoolConfig::new(4)),
+			..Config::default()
+		}
+		.create_pool(Some(Runtime::Tokio1), NoTls)
+		.unwrap();
+
+		let rendered = metrics.render(&pool, None).unwrap();
+
+		assert!(rendered
+			.contains(r#"canister_http_requests_total{method="GET",route="/v2/",status="200"} 1"#));
+		assert!(rendered
+			.contains(r#"canister_ingest_forwards_total{result="failure",sink="vector"} 1"#));
+		assert!(rendered
+			.contains(r#"canister_db_query_duration_seconds_count{handler="package_lookup"} 1"#));
+		assert!(rendered.contains("canister_db_pool_max_size 4"));
+		assert!(rendered.contains("canister_safety_list_age_seconds NaN"));
+	}
+}
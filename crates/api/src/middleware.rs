@@ -0,0 +1,144 @@
+use crate::AppState;
+use axum::{
+	extract::{MatchedPath, State},
+	http::{HeaderMap, HeaderValue, Request},
+	middleware::Next,
+	response::Response,
+};
+use sentry::{Hub, SentryFutureExt};
+use std::{sync::Arc, time::Instant};
+use tracing::{info, info_span, Instrument};
+use uuid::Uuid;
+
+/// Header that ties a request to its logs and Sentry events
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// Longest request ID accepted from a client before one is generated instead
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// ID of the request being handled, available to handlers as an extension
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Reuses the request ID sent by the client, generating one if it's missing
+/// or isn't something that can be safely logged and echoed back
+fn request_id(headers: &HeaderMap) -> String {
+	match headers
+		.get(REQUEST_ID_HEADER)
+		.and_then(|value| value.to_str().ok())
+	{
+		Some(id)
+			if !id.is_empty()
+				&& id.len() <= MAX_REQUEST_ID_LENGTH
+				&& id.bytes().all(|byte| byte.is_ascii_graphic()) =>
+		{
+			id.to_string()
+		}
+		_ => Uuid::new_v4().to_string(),
+	}
+}
+
+/// Runs every request in a span carrying its ID and a Sentry hub tagged with
+/// it, then writes an access log line once the response is ready
+pub async fn trace_middleware<B>(
+	State(state): State<AppState>,
+	mut request: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let started = Instant::now();
+	let id = request_id(request.headers());
+	let method = request.method().clone();
+
+	// Templates keep the logs groupable, requests that match no route share one
+	let route = match request.extensions().get::<MatchedPath>() {
+		Some(path) => path.as_str().to_string(),
+		None => "unmatched".to_string(),
+	};
+
+	let span = info_span!("request", request_id = %id);
+	let hub = Arc::new(Hub::new_from_top(Hub::current()));
+	hub.configure_scope(|scope| scope.set_tag("request_id", &id));
+
+	request.extensions_mut().insert(RequestId(id.clone()));
+	let mut response = next
+		.run(request)
+		.instrument(span.clone())
+		.bind_hub(hub)
+		.await;
+
+	let latency = started.elapsed();
+	state
+		.metrics
+		.observe_request(method.as_str(), &route, response.status().as_u16(), latency);
+
+	span.in_scope(|| {
+		info!(
+			target: "access",
+			method = %method,
+			route = %route,
+			status = response.status().as_u16(),
+			latency_ms = latency.as_secs_f64() * 1000.0,
+			pod = %state.config.pod_name,
+			"Handled request"
+		);
+	});
+
+	if let Ok(id) = HeaderValue::from_str(&id) {
+		response.headers_mut().insert(REQUEST_ID_HEADER, id);
+	}
+
+	response
+}
+
+pub async fn cors_middleware<B>(
+	State(state): State<AppState>,
+	request: Request<B>,
+	next: Next<B>,
+) -> Response {
+	let mut response = next.run(request).await;
+	let headers = response.headers_mut();
+
+	headers.insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
+	headers.insert(
+		"Access-Control-Allow-Methods",
+		HeaderValue::from_static("GET, POST, OPTIONS"),
+	);
+	headers.insert(
+		"Access-Control-Allow-Headers",
+		HeaderValue::from_static("Content-Type, *"),
+	);
+	headers.insert(
+		"Access-Control-Expose-Headers",
+		HeaderValue::from_static(REQUEST_ID_HEADER),
+	);
+
+	// Also add the X-Served-By header, X-Request-ID is set by trace_middleware
+	// and exposed above so browser clients can quote it in bug reports
+	headers.insert(
+		"X-Served-By",
+		HeaderValue::from_str(&state.config.pod_name)
+			.unwrap_or(HeaderValue::from_static("unknown")),
+	);
+	response
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn request_ids_are_reused_when_safe() {
+		let mut headers = HeaderMap::new();
+		headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("abc-123"));
+		assert_eq!(request_id(&headers), "abc-123");
+
+		for value in ["", "has spaces", &"a".repeat(MAX_REQUEST_ID_LENGTH + 1)] {
+			headers.insert(REQUEST_ID_HEADER, HeaderValue::from_str(value).unwrap());
+			let id = request_id(&headers);
+			assert_ne!(id, value);
+			assert!(Uuid::parse_str(&id).is_ok());
+		}
+
+		assert!(Uuid::parse_str(&request_id(&HeaderMap::new())).is_ok());
+	}
+}
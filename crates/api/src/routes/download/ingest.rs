@@ -1,25 +1,47 @@
 use crate::{
-	helpers::{pg_client, responses},
-	utility::{load_runtime_config, parse_user_agent},
+	helpers::{
+		ingest::{DownloadEvent, Enqueued},
+		pg_client, responses,
+	},
+	server::ClientAddr,
+	utility::{client_ip, normalize_uri, parse_user_agent},
+	AppState,
 };
+use anyhow::Result;
 use axum::{
-	http::{HeaderMap, HeaderValue, StatusCode},
-	response::IntoResponse,
+	extract::{rejection::JsonRejection, ConnectInfo, State},
+	http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode},
+	response::{IntoResponse, Response},
 	Json,
 };
 use chrono::Utc;
-use once_cell::sync::OnceCell;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::to_value;
-use std::sync::OnceLock;
+use std::{
+	collections::{hash_map::DefaultHasher, HashSet},
+	hash::{Hash, Hasher},
+	time::{Duration, Instant},
+};
+use tracing::{error, warn};
+use url::Url;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BadRequest {
-	status: String,
-	date: String,
-	error: String,
-}
+/// Longest value accepted for the package and repository fields of an event
+const MAX_FIELD_LENGTH: usize = 256;
+
+/// Longest repository URI accepted
+const MAX_URI_LENGTH: usize = 2048;
+
+/// Longest client hint header accepted, longer optional ones become unknown
+const MAX_HEADER_LENGTH: usize = 512;
+
+/// Client hints that together tell devices behind the same address apart
+const DEVICE_HEADERS: [&str; 6] = [
+	"Sec-CH-UA",
+	"Sec-CH-UA-Arch",
+	"Sec-CH-UA-Bitness",
+	"Sec-CH-UA-Model",
+	"Sec-CH-UA-Platform",
+	"Sec-CH-UA-Platform-Version",
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Payload {
@@ -32,60 +54,286 @@ pub struct Payload {
 	pub repository_component: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct DownloadEvent {
-	package_id: String,
-	package_version: String,
-	package_author: String,
-	package_maintainer: String,
-	repository_uri: String,
-	repository_suite: String,
-	repository_component: String,
-
-	client: String,
-	client_version: String,
-	jailbreak: String,
-	jailbreak_version: String,
-	distribution: String,
-	distribution_version: String,
-
-	client_architecture: String,
-	client_bitness: String,
-	device: String,
-	device_platform: String,
-	device_version: String,
-
-	database_uuid: Option<String>,
-	time: i64,
+/// Whether an event could be matched to a package in the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Resolution {
+	Resolved,
+	/// No visible package has this version in the given repository
+	NotFound,
+	/// The database couldn't be queried, so the package is unknown
+	LookupFailed,
+}
+
+#[derive(Debug, Serialize)]
+struct Accepted {
+	/// Position of the event in the request
+	index: usize,
+	resolution: Resolution,
 }
 
-static HTTP: OnceCell<Client> = OnceCell::new();
-static VECTOR_URL: OnceLock<String> = OnceLock::new();
+#[derive(Debug, Serialize)]
+struct Rejected {
+	index: usize,
+	reason: String,
+}
+
+/// What became of every event in a request, without echoing them back
+#[derive(Debug, Serialize)]
+struct IngestSummary {
+	accepted: Vec<Accepted>,
+	rejected: Vec<Rejected>,
+}
+
+/// Finds the database ID of every entry's package in a single query
+/// Entries are matched on their repository too, since a package ID and version
+/// can be published by more than one repository
+async fn lookup_packages(state: &AppState, entries: &[&Payload]) -> Result<Vec<Option<String>>> {
+	let mut database_uuids = vec![None; entries.len()];
+	if entries.is_empty() {
+		return Ok(database_uuids);
+	}
+
+	let package_ids = entries
+		.iter()
+		.map(|entry| entry.package_id.as_str())
+		.collect::<Vec<&str>>();
+
+	let versions = entries
+		.iter()
+		.map(|entry| entry.package_version.as_str())
+		.collect::<Vec<&str>>();
+
+	let repositories = entries
+		.iter()
+		.map(|entry| normalize_uri(&entry.repository_uri))
+		.collect::<Vec<String>>();
+
+	let pg_client = pg_client(&state.pool).await?;
+	let rows = state
+		.metrics
+		.time_query(
+			"download_ingest",
+			pg_client.query(
+				"
+                SELECT DISTINCT ON (entry.index) entry.index, package.id
+                FROM
+                    unnest($1::text[], $2::text[], $3::text[])
+                    WITH ORDINALITY AS entry(package_id, version, repository_uri, index)
+                INNER JOIN
+                    package ON
+                    package.package_id = entry.package_id
+                    AND package.version = entry.version
+                INNER JOIN
+                    repository ON
+                    package.repository_id = repository.id
+                WHERE
+                    package.visible = true
+                    AND rtrim(regexp_replace(lower(repository.uri), '^https?://', ''), '/') = entry.repository_uri
+                ORDER BY
+                    entry.index, package.id
+                ",
+				&[&package_ids, &versions, &repositories],
+			),
+		)
+		.await?;
+
+	for row in rows {
+		// Ordinality counts from 1
+		let index = row.get::<_, i64>("index") as usize - 1;
+		database_uuids[index] = Some(row.get("id"));
+	}
+
+	Ok(database_uuids)
+}
 
 fn try_get_header(header: Option<&HeaderValue>) -> String {
 	match header {
-		Some(header) => match header.to_str() {
+		Some(header) if header.len() <= MAX_HEADER_LENGTH => match header.to_str() {
 			Ok(header) => header.to_string(),
 			Err(_) => "unknown".to_string(),
 		},
-		None => "unknown".to_string(),
+		_ => "unknown".to_string(),
+	}
+}
+
+/// Checks an event before it's accepted, explaining why it isn't
+fn validate(entry: &Payload) -> Result<(), String> {
+	for (name, value) in [
+		("package_id", Some(&entry.package_id)),
+		("package_version", Some(&entry.package_version)),
+		("package_author", entry.package_author.as_ref()),
+		("package_maintainer", entry.package_maintainer.as_ref()),
+		("repository_suite", entry.repository_suite.as_ref()),
+		("repository_component", entry.repository_component.as_ref()),
+	] {
+		let length = value.map_or(1, |value| value.chars().count());
+		if length == 0 || length > MAX_FIELD_LENGTH {
+			return Err(format!(
+				"Field \'{}\' must be between 1 and {} characters",
+				name, MAX_FIELD_LENGTH
+			));
+		}
+	}
+
+	if entry.repository_uri.len() > MAX_URI_LENGTH {
+		return Err(format!(
+			"Field \'repository_uri\' must be at most {} characters",
+			MAX_URI_LENGTH
+		));
+	}
+
+	match Url::parse(&entry.repository_uri) {
+		Ok(uri) if matches!(uri.scheme(), "http" | "https") && uri.host().is_some() => Ok(()),
+		_ => Err("Field \'repository_uri\' must be an http or https URL".to_string()),
+	}
+}
+
+/// Identifies a device by its address and client hints, since clients don't
+/// send anything more specific
+fn device_key(ip: &str, headers: &HeaderMap) -> String {
+	let mut hasher = DefaultHasher::new();
+	ip.hash(&mut hasher);
+
+	for name in DEVICE_HEADERS {
+		headers
+			.get(name)
+			.map(HeaderValue::as_bytes)
+			.hash(&mut hasher);
 	}
+
+	format!("{:016x}", hasher.finish())
+}
+
+/// Identifies an event for deduplication, repeats from the same device match
+fn event_key(device: &str, entry: &Payload) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	device.hash(&mut hasher);
+	entry.package_id.hash(&mut hasher);
+	entry.package_version.hash(&mut hasher);
+	normalize_uri(&entry.repository_uri).hash(&mut hasher);
+	hasher.finish()
 }
 
-pub async fn ingest(headers: HeaderMap, body: Option<Json<Vec<Payload>>>) -> impl IntoResponse {
+fn too_many_requests(retry_after: Duration) -> Response {
+	let mut response = responses::error(
+		StatusCode::TOO_MANY_REQUESTS,
+		"Too many download events, try again later",
+	)
+	.into_response();
+
+	// Rounded up so retrying right on time succeeds
+	let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+	response
+		.headers_mut()
+		.insert(RETRY_AFTER, HeaderValue::from(seconds));
+
+	response
+}
+
+pub async fn ingest(
+	State(state): State<AppState>,
+	connect_info: Option<ConnectInfo<ClientAddr>>,
+	headers: HeaderMap,
+	body: Result<Json<Vec<Payload>>, JsonRejection>,
+) -> Response {
 	let body = match body {
-		Some(body) => body,
-		None => return responses::error(StatusCode::BAD_REQUEST, "Invalid request body"),
+		Ok(body) => body,
+		Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+			return responses::error(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large")
+				.into_response()
+		}
+		Err(_) => {
+			return responses::error(StatusCode::BAD_REQUEST, "Invalid request body")
+				.into_response()
+		}
 	};
 
+	if body.len() > state.config.ingest_max_events {
+		return responses::error(
+			StatusCode::PAYLOAD_TOO_LARGE,
+			format!(
+				"Requests must contain at most {} events",
+				state.config.ingest_max_events
+			),
+		)
+		.into_response();
+	}
+
 	let user_agent = match headers.get("Sec-CH-UA") {
 		Some(user_agent) => match user_agent.to_str() {
-			Ok(user_agent) => parse_user_agent(user_agent),
-			Err(_) => return responses::error(StatusCode::BAD_REQUEST, "Invalid user agent"),
+			Ok(user_agent) if user_agent.len() <= MAX_HEADER_LENGTH => parse_user_agent(user_agent),
+			_ => {
+				return responses::error(StatusCode::BAD_REQUEST, "Invalid user agent")
+					.into_response()
+			}
 		},
-		None => return responses::error(StatusCode::BAD_REQUEST, "Missing user agent"),
+		None => {
+			return responses::error(StatusCode::BAD_REQUEST, "Missing user agent").into_response()
+		}
+	};
+
+	if user_agent.is_empty() {
+		return responses::error(StatusCode::BAD_REQUEST, "Invalid user agent").into_response();
+	}
+
+	// Every event counts against the limits, valid or not
+	let now = Instant::now();
+	let peer = connect_info.and_then(|ConnectInfo(ClientAddr(peer))| peer);
+	let ip = match client_ip(&headers, peer, state.config.trust_forwarded_for) {
+		Some(ip) => ip.to_string(),
+		None => "unknown".to_string(),
 	};
 
+	let device = device_key(&ip, &headers);
+	for (limiter, key) in [
+		(&state.ingest_limits.ip, &ip),
+		(&state.ingest_limits.device, &device),
+	] {
+		if let Err(retry_after) = limiter.acquire(key, body.len(), now) {
+			state
+				.metrics
+				.observe_ingest_rejection("rate_limited", body.len());
+			return too_many_requests(retry_after);
+		}
+	}
+
+	let mut accepted: Vec<(usize, &Payload, u64)> = vec![];
+	let mut rejected: Vec<Rejected> = vec![];
+	let mut keys: HashSet<u64> = HashSet::new();
+
+	for (index, entry) in body.iter().enumerate() {
+		if let Err(reason) = validate(entry) {
+			state.metrics.observe_ingest_rejection("invalid", 1);
+			rejected.push(Rejected { index, reason });
+			continue;
+		}
+
+		// Repeats within the same request are caught alongside earlier ones
+		let key = event_key(&device, entry);
+		if state.ingest_limits.recent.is_duplicate(key, now) || !keys.insert(key) {
+			state.metrics.observe_ingest_rejection("duplicate", 1);
+			rejected.push(Rejected {
+				index,
+				reason: "Duplicate of a recent event".to_string(),
+			});
+			continue;
+		}
+
+		accepted.push((index, entry, key));
+	}
+
+	if accepted.is_empty() {
+		return responses::data(
+			StatusCode::ACCEPTED,
+			IngestSummary {
+				accepted: vec![],
+				rejected,
+			},
+		)
+		.into_response();
+	}
+
 	let architecture = try_get_header(headers.get("Sec-CH-UA-Arch"));
 	let bitness = try_get_header(headers.get("Sec-CH-UA-Bitness"));
 	let model = try_get_header(headers.get("Sec-CH-UA-Model"));
@@ -111,9 +359,32 @@ pub async fn ingest(headers: HeaderMap, body: Option<Json<Vec<Payload>>>) -> imp
 		None => ("unknown".to_string(), "unknown".to_string()),
 	};
 
+	let entries = accepted
+		.iter()
+		.map(|(_, entry, _)| *entry)
+		.collect::<Vec<&Payload>>();
+
+	let resolutions = match lookup_packages(&state, &entries).await {
+		Ok(database_uuids) => database_uuids
+			.into_iter()
+			.map(|database_uuid| match database_uuid {
+				Some(database_uuid) => (Some(database_uuid), Resolution::Resolved),
+				None => (None, Resolution::NotFound),
+			})
+			.collect::<Vec<_>>(),
+		Err(e) => {
+			// Events are still worth recording without the package they belong to
+			error!(target: "db", "Failed to query database: {}", e);
+			entries
+				.iter()
+				.map(|_| (None, Resolution::LookupFailed))
+				.collect::<Vec<_>>()
+		}
+	};
+
 	let mut events: Vec<DownloadEvent> = vec![];
 
-	for entry in body.iter() {
+	for (entry, (database_uuid, _)) in entries.iter().zip(&resolutions) {
 		let package_id = &entry.package_id.to_string();
 		let package_version = &entry.package_version.to_string();
 
@@ -138,35 +409,6 @@ pub async fn ingest(headers: HeaderMap, body: Option<Json<Vec<Payload>>>) -> imp
 			None => "none".to_string(),
 		};
 
-		let database_uuid = match pg_client().await {
-			Ok(pg_client) => {
-				let package_search = pg_client
-					.query(
-						"
-                        SELECT id FROM package
-                        WHERE
-                            visible = true
-                            AND package_id = $1
-                            AND version = $2
-                        ",
-						&[&package_id, &package_version],
-					)
-					.await;
-
-				match package_search {
-					Ok(package_search) => {
-						if package_search.is_empty() {
-							None
-						} else {
-							Some(package_search[0].get("id"))
-						}
-					}
-					Err(_) => None,
-				}
-			}
-			Err(_) => None,
-		};
-
 		let event = DownloadEvent {
 			package_id: package_id.to_string(),
 			package_version: package_version.to_string(),
@@ -188,63 +430,97 @@ pub async fn ingest(headers: HeaderMap, body: Option<Json<Vec<Payload>>>) -> imp
 			device_platform: platform.clone(),
 			device_version: platform_version.clone(),
 
-			database_uuid,
+			database_uuid: database_uuid.clone(),
 			time: Utc::now().timestamp(),
 		};
 
 		events.push(event);
 	}
 
-	let return_value = match to_value(events) {
-		Ok(return_value) => return_value,
-		Err(_) => {
-			return responses::error(
-				StatusCode::INTERNAL_SERVER_ERROR,
-				"Failed to serialize events",
-			);
+	// Events are delivered to the sinks in the background once they're spooled
+	match state.ingest.enqueue(&events).await {
+		Ok(Enqueued::Accepted) => {
+			let keys = accepted
+				.iter()
+				.map(|(_, _, key)| *key)
+				.collect::<Vec<u64>>();
+
+			// Only queued events count, so a retry after a full queue isn't a duplicate
+			state.ingest_limits.recent.remember(&keys, now);
+
+			let accepted = accepted
+				.iter()
+				.zip(&resolutions)
+				.map(|((index, _, _), (_, resolution))| Accepted {
+					index: *index,
+					resolution: *resolution,
+				})
+				.collect::<Vec<Accepted>>();
+
+			responses::data(StatusCode::ACCEPTED, IngestSummary { accepted, rejected })
+				.into_response()
 		}
-	};
+		Ok(Enqueued::Full) => {
+			warn!(target: "ingest", "Dropped {} events, the queue is full", events.len());
+			responses::error(StatusCode::SERVICE_UNAVAILABLE, "Ingest queue is full")
+				.into_response()
+		}
+		Err(e) => {
+			error!(target: "ingest", "Failed to spool events: {}", e);
+			responses::error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to queue events")
+				.into_response()
+		}
+	}
+}
 
-	let http_client = match HTTP.get_or_try_init(|| {
-		Client::builder()
-			.timeout(std::time::Duration::from_secs(5))
-			.build()
-	}) {
-		Ok(http_client) => http_client,
-		Err(_) => {
-			return responses::error(
-				StatusCode::INTERNAL_SERVER_ERROR,
-				"Failed to initialize HTTP client",
-			);
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn payload(package_id: &str, repository_uri: &str) -> Payload {
+		Payload {
+			package_id: package_id.to_string(),
+			package_version: "1.0".to_string(),
+			package_author: None,
+			package_maintainer: None,
+			repository_uri: repository_uri.to_string(),
+			repository_suite: None,
+			repository_component: Some(String::new()),
 		}
-	};
+	}
 
-	let vector_url = VECTOR_URL.get_or_init(|| {
-		let config = load_runtime_config();
-		config.vector_url
-	});
+	#[test]
+	fn validates_fields_and_uris() {
+		let mut entry = payload("ws.hbang.common", "https://repo.chariz.com/");
+		assert_eq!(
+			validate(&entry),
+			Err("Field 'repository_component' must be between 1 and 256 characters".to_string())
+		);
 
-	let cloned_payload = return_value.clone();
-	let response = http_client
-		.post(vector_url)
-		.json(&cloned_payload)
-		.send()
-		.await;
+		entry.repository_component = None;
+		assert!(validate(&entry).is_ok());
 
-	match response {
-		Ok(_) => (),
-		Err(_) => {
-			return responses::error(
-				StatusCode::INTERNAL_SERVER_ERROR,
-				"Failed to send event to ingest",
-			);
+		for uri in ["repo.chariz.com", "ftp://repo.chariz.com", "https://"] {
+			entry.repository_uri = uri.to_string();
+			assert!(validate(&entry).is_err(), "{} should be rejected", uri);
 		}
-	}
 
-	responses::data(StatusCode::OK, return_value)
-}
+		entry.package_id = "a".repeat(MAX_FIELD_LENGTH + 1);
+		assert!(validate(&entry).is_err());
+	}
 
-// TODO: Implement
-pub async fn ingest_healthy() -> bool {
-	true
+	#[test]
+	fn repeated_events_share_a_key() {
+		let device = device_key("203.0.113.7", &HeaderMap::new());
+		let first = payload("ws.hbang.common", "https://repo.chariz.com/");
+		let second = payload("ws.hbang.common", "http://repo.chariz.com");
+		let other = payload("com.tigisoftware.filza", "https://repo.chariz.com/");
+
+		assert_eq!(event_key(&device, &first), event_key(&device, &second));
+		assert_ne!(event_key(&device, &first), event_key(&device, &other));
+		assert_ne!(
+			event_key(&device, &first),
+			event_key(&device_key("198.51.100.2", &HeaderMap::new()), &first)
+		);
+	}
 }
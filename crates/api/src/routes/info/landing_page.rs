@@ -1,13 +1,12 @@
 use crate::helpers::responses;
-use crate::utility::load_runtime_config;
-use axum::{http::StatusCode, response::IntoResponse};
+use crate::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use chrono::Datelike;
 use chrono::Utc;
 use serde_json::json;
-use std::env::var;
 
-pub async fn landing_page() -> impl IntoResponse {
-	let config = load_runtime_config();
+pub async fn landing_page(State(state): State<AppState>) -> impl IntoResponse {
+	let config = &state.config;
 
 	let name = format!("{} ({})", config.meta_name, config.meta_code);
 
@@ -25,7 +24,7 @@ pub async fn landing_page() -> impl IntoResponse {
 		env!("VERGEN_RUSTC_LLVM_VERSION")
 	);
 
-	let runtime = format!("k8s-{}", var("POD_NAME").unwrap_or("unknown".to_string()));
+	let runtime = format!("k8s-{}", config.pod_name);
 	let copyright = config
 		.meta_copyright
 		.replace("{year}", &Utc::now().year().to_string());
@@ -0,0 +1,46 @@
+use crate::{helpers::responses, metrics::METRICS_CONTENT_TYPE, utility::secrets_match, AppState};
+use axum::{
+	extract::State,
+	http::{header, HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+};
+use tracing::error;
+
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+	let expected = &state.config.metrics_token;
+	if expected.is_empty() {
+		return responses::error(StatusCode::NOT_FOUND, "Metrics are disabled").into_response();
+	}
+
+	let token = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+
+	match token {
+		Some(token) if secrets_match(expected.as_bytes(), token.as_bytes()) => {}
+		_ => {
+			return responses::error(StatusCode::UNAUTHORIZED, "Invalid metrics token")
+				.into_response()
+		}
+	}
+
+	let safety_list_fetched = state.caches.piracy_fetched_at.get().copied();
+
+	match state.metrics.render(&state.pool, safety_list_fetched) {
+		Ok(body) => (
+			StatusCode::OK,
+			[(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+			body,
+		)
+			.into_response(),
+		Err(e) => {
+			error!(target: "metrics", "Failed to render metrics: {}", e);
+			responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to render metrics",
+			)
+			.into_response()
+		}
+	}
+}
@@ -1,9 +1,11 @@
 mod health_check;
 mod landing_page;
+mod metrics;
 mod openapi_json;
 mod openapi_yaml;
 
 pub use self::health_check::*;
 pub use self::landing_page::*;
+pub use self::metrics::*;
 pub use self::openapi_json::*;
 pub use self::openapi_yaml::*;
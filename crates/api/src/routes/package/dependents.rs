@@ -0,0 +1,143 @@
+use crate::{
+	helpers::{pg_client, responses},
+	types::{Dependent, DependentRelations, FromRow, Package, PackageRefs},
+	utility::{
+		client_architecture, compatible_architectures, installable_architectures,
+		package_relations, relations_on,
+	},
+	AppState,
+};
+use axum::{
+	extract::{Path, Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Deserialize)]
+pub struct DependentsParams {
+	arch: Option<String>,
+}
+
+pub async fn dependents(
+	State(state): State<AppState>,
+	package: Path<String>,
+	query: Query<DependentsParams>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	let architecture = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(architecture) => architecture,
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = architecture.as_deref().map(installable_architectures);
+
+	// Relations are split into package names the same way `parse_relations` does,
+	// so `libfoo` doesn't match a dependency on `libfoo2`
+	let rows = match pg_client(&state.pool).await {
+		Ok(pg_client) => {
+			match state
+				.metrics
+				.time_query(
+					"package_dependents",
+					pg_client.query(
+						"
+                        SELECT * FROM package
+                        WHERE
+                            visible = true
+                            AND latest_version = true
+                            AND package_id <> $1
+                            AND EXISTS (
+                                SELECT 1 FROM regexp_split_to_table(
+                                    concat_ws(',', depends, pre_depends), '[,|]'
+                                ) AS dependency
+                                WHERE substring(dependency FROM '^\\s*([^\\s(\\[<:]+)') = $1
+                            )
+                            AND ($2::text[] IS NULL OR architecture = ANY($2))
+                        ORDER BY
+                            quality ASC,
+                            package_id ASC
+                    ",
+						&[&package.to_string(), &architectures],
+					),
+				)
+				.await
+			{
+				Ok(rows) => rows,
+				Err(e) => {
+					error!(target: "db", "Failed to query database: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to query database",
+					);
+				}
+			}
+		}
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let packages = match Package::from_rows(&rows) {
+		Ok(packages) => packages,
+		Err(e) => {
+			error!(target: "db", "Failed to decode package: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let dependents = packages
+		.into_iter()
+		.filter_map(|dependent| {
+			// Only the relations that mention the package are reported
+			let relations = package_relations(&dependent);
+			let dependency = DependentRelations {
+				depends: relations_on(&relations.depends, &package)
+					.into_iter()
+					.cloned()
+					.collect(),
+				pre_depends: relations_on(&relations.pre_depends, &package)
+					.into_iter()
+					.cloned()
+					.collect(),
+			};
+
+			if dependency.depends.is_empty() && dependency.pre_depends.is_empty() {
+				return None;
+			}
+
+			let base = Package {
+				compatible_architectures: Some(
+					compatible_architectures(&dependent.architecture)
+						.iter()
+						.map(|architecture| architecture.to_string())
+						.collect(),
+				),
+				relations: Some(relations),
+				refs: Some(PackageRefs {
+					meta: Some(format!(
+						"{}/jailbreak/package/{}",
+						state.config.api_endpoint, dependent.package_id
+					)),
+					repo: format!(
+						"{}/jailbreak/repository/{}",
+						state.config.api_endpoint, dependent.repository_id
+					),
+				}),
+				..dependent
+			};
+
+			Some(Dependent { base, dependency })
+		})
+		.collect::<Vec<Dependent>>();
+
+	responses::data_with_count(StatusCode::OK, &dependents, dependents.len())
+}
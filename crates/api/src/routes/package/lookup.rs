@@ -1,31 +1,63 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json},
+	helpers::{pg_client, responses},
+	types::{FromRow, Package, PackageRefs},
+	utility::{
+		client_architecture, compare_versions, compatible_architectures, installable_architectures,
+		package_relations,
+	},
+	AppState,
 };
-use axum::{extract::Path, http::StatusCode, response::IntoResponse};
-use serde_json::{json, Value};
+use axum::{
+	extract::{Path, Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+};
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Deserialize)]
+pub struct LookupParams {
+	arch: Option<String>,
+}
 
-pub async fn lookup(package: Path<String>) -> impl IntoResponse {
-	let packages = match pg_client().await {
+pub async fn lookup(
+	State(state): State<AppState>,
+	package: Path<String>,
+	query: Query<LookupParams>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	let architecture = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(architecture) => architecture,
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = architecture.as_deref().map(installable_architectures);
+
+	let rows = match pg_client(&state.pool).await {
 		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
+			match state
+				.metrics
+				.time_query(
+					"package_lookup",
+					pg_client.query(
+						"
                         SELECT * FROM package
                         WHERE
                             visible = true
                             AND package_id = $1
+                            AND ($2::text[] IS NULL OR architecture = ANY($2))
                         ORDER BY
                             latest_version DESC,
                             quality ASC
                     ",
-					&[&package.to_string()],
+						&[&package.to_string(), &architectures],
+					),
 				)
 				.await
 			{
 				Ok(rows) => rows,
 				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
+					error!(target: "db", "Failed to query database: {}", e);
 					return responses::error(
 						StatusCode::INTERNAL_SERVER_ERROR,
 						"Failed to query database",
@@ -34,7 +66,7 @@ pub async fn lookup(package: Path<String>) -> impl IntoResponse {
 			}
 		}
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to query database: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -42,53 +74,44 @@ pub async fn lookup(package: Path<String>) -> impl IntoResponse {
 		}
 	};
 
-	if packages.is_empty() {
+	if rows.is_empty() {
 		return responses::error(StatusCode::NOT_FOUND, "Package not found");
 	}
 
-	responses::data_with_count(
-		StatusCode::OK,
-		packages
-			.iter()
-			.map(|row| {
-				let id: String = row.get("repository_id");
-				return merge_json(
-					row_to_value(row),
-					json!({
-						"refs": {
-							"repo": format!("{}/jailbreak/repository/{}", api_endpoint(), id)
-						}
-					}),
-				);
-			})
-			.collect::<Vec<Value>>(),
-		packages.len(),
-	)
-}
+	let mut packages = match Package::from_rows(&rows) {
+		Ok(packages) => packages,
+		Err(e) => {
+			error!(target: "db", "Failed to decode package: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
 
-pub async fn lookup_healthy() -> bool {
-	match pg_client().await {
-		Ok(pg_client) => {
-			let rows = pg_client
-				.query(
-					"
-                        SELECT * FROM package
-                        WHERE
-                            visible = true
-                            AND package_id = 'ws.hbang.common'
-                        ORDER BY
-                            latest_version DESC,
-                            quality ASC
-                    ",
-					&[],
-				)
-				.await;
+	// Newest version first, the sort is stable so ties keep the query order
+	packages.sort_by(|a, b| compare_versions(&b.version, &a.version));
 
-			match rows {
-				Ok(_) => true,
-				Err(_) => false,
-			}
-		}
-		Err(_) => false,
-	}
+	let packages = packages
+		.into_iter()
+		.map(|package| Package {
+			compatible_architectures: Some(
+				compatible_architectures(&package.architecture)
+					.iter()
+					.map(|architecture| architecture.to_string())
+					.collect(),
+			),
+			relations: Some(package_relations(&package)),
+			refs: Some(PackageRefs {
+				meta: None,
+				repo: format!(
+					"{}/jailbreak/repository/{}",
+					state.config.api_endpoint, package.repository_id
+				),
+			}),
+			..package
+		})
+		.collect::<Vec<Package>>();
+
+	responses::data_with_count(StatusCode::OK, &packages, packages.len())
 }
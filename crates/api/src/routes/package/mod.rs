@@ -1,7 +1,15 @@
+mod dependents;
 mod lookup;
 mod multi_lookup;
+mod resolve;
 mod search;
+mod updates;
+mod versions;
 
+pub use self::dependents::*;
 pub use self::lookup::*;
 pub use self::multi_lookup::*;
+pub use self::resolve::*;
 pub use self::search::*;
+pub use self::updates::*;
+pub use self::versions::*;
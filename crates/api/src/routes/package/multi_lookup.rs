@@ -1,18 +1,33 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json},
+	helpers::{pg_client, responses},
+	types::{FromRow, Package, PackageRefs},
+	utility::{
+		client_architecture, compatible_architectures, installable_architectures, package_relations,
+	},
+	AppState,
 };
-use axum::{extract::Query, http::StatusCode, response::IntoResponse};
+use axum::{
+	extract::{Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+};
+use deadpool_postgres::tokio_postgres::Row;
 use serde::Deserialize;
-use serde_json::{json, Value};
+use std::cmp::Ordering;
+use tracing::error;
 
 #[derive(Deserialize)]
 pub struct MultiLookupParams {
 	ids: Option<String>,
 	priority: Option<String>,
+	arch: Option<String>,
 }
 
-pub async fn multi_lookup(query: Query<MultiLookupParams>) -> impl IntoResponse {
+pub async fn multi_lookup(
+	State(state): State<AppState>,
+	query: Query<MultiLookupParams>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
 	let ids = match &query.ids {
 		Some(ids) => {
 			let ids: Vec<String> = ids.split(',').map(|id| id.to_string()).collect();
@@ -28,11 +43,21 @@ pub async fn multi_lookup(query: Query<MultiLookupParams>) -> impl IntoResponse
 		None => "default",
 	};
 
-	let mut packages = match pg_client().await {
+	let architecture = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(architecture) => architecture,
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = architecture.as_deref().map(installable_architectures);
+
+	let mut rows = match pg_client(&state.pool).await {
 		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
+			match state
+				.metrics
+				.time_query(
+					"package_multi_lookup",
+					pg_client.query(
+						"
                         SELECT package.*, repository.bootstrap
                         FROM package
                         LEFT JOIN
@@ -42,16 +67,18 @@ pub async fn multi_lookup(query: Query<MultiLookupParams>) -> impl IntoResponse
                             package.visible = true
                             AND latest_version = true
                             AND package_id = ANY($1)
+                            AND ($2::text[] IS NULL OR architecture = ANY($2))
                         ORDER BY
                             quality ASC
                     ",
-					&[&ids],
+						&[&ids, &architectures],
+					),
 				)
 				.await
 			{
 				Ok(rows) => rows,
 				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
+					error!(target: "db", "Failed to query database: {}", e);
 					return responses::error(
 						StatusCode::INTERNAL_SERVER_ERROR,
 						"Failed to query database",
@@ -60,7 +87,7 @@ pub async fn multi_lookup(query: Query<MultiLookupParams>) -> impl IntoResponse
 			}
 		}
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to query database: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -68,96 +95,76 @@ pub async fn multi_lookup(query: Query<MultiLookupParams>) -> impl IntoResponse
 		}
 	};
 
-	if packages.is_empty() {
+	if rows.is_empty() {
 		return responses::error(StatusCode::NOT_FOUND, "Packages not found");
 	}
 
+	rows.sort_by(|a, b| compare_priority(a, b, priority));
+
+	let packages = match Package::from_rows(&rows) {
+		Ok(packages) => packages,
+		Err(e) => {
+			error!(target: "db", "Failed to decode package: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
 	let mut ids: Vec<String> = packages
 		.iter()
-		.map(|package| package.get("package_id"))
+		.map(|package| package.package_id.clone())
 		.collect();
 
-	packages.sort_by(|a, b| {
-		// If the priority is bootstrap, prioritize package.repository.is_bootstrap
-		if priority == "bootstrap" {
-			let a_bootstrap: bool = a.get("bootstrap");
-			let b_bootstrap: bool = b.get("bootstrap");
-
-			if a_bootstrap && !b_bootstrap {
-				return std::cmp::Ordering::Less;
-			} else if !a_bootstrap && b_bootstrap {
-				return std::cmp::Ordering::Greater;
+	let packages = packages
+		.into_iter()
+		.filter(|package| {
+			if ids.contains(&package.package_id) {
+				ids.retain(|id| id != &package.package_id);
+				return true;
 			}
-		}
-
-		let a_quality: i32 = a.get("quality");
-		let b_quality: i32 = b.get("quality");
-
-		// If the priority is default, prioritize package.repository_tier
-		if a_quality < b_quality {
-			return std::cmp::Ordering::Less;
-		} else if a_quality > b_quality {
-			return std::cmp::Ordering::Greater;
-		}
+			false
+		})
+		.map(|package| Package {
+			compatible_architectures: Some(
+				compatible_architectures(&package.architecture)
+					.iter()
+					.map(|architecture| architecture.to_string())
+					.collect(),
+			),
+			relations: Some(package_relations(&package)),
+			refs: Some(PackageRefs {
+				meta: None,
+				repo: format!(
+					"{}/jailbreak/repository/{}",
+					state.config.api_endpoint, package.repository_id
+				),
+			}),
+			..package
+		})
+		.collect::<Vec<Package>>();
 
-		return std::cmp::Ordering::Equal;
-	});
-
-	responses::data_with_count(
-		StatusCode::OK,
-		packages
-			.iter()
-			.filter(|package| {
-				let package_id: String = package.get("package_id");
-				if ids.contains(&package_id) {
-					ids.retain(|id| id != &package_id);
-					return true;
-				}
-				return false;
-			})
-			.map(|package| {
-				let repository_id: String = package.get("repository_id");
-				return merge_json(
-					row_to_value(package),
-					json!({
-						"refs": {
-							"repo": format!("{}/jailbreak/repository/{}", api_endpoint(), repository_id)
-						}
-					}),
-				);
-			})
-			.collect::<Vec<Value>>(),
-		packages.len(),
-	)
+	responses::data_with_count(StatusCode::OK, &packages, packages.len())
 }
 
-pub async fn multi_lookup_healthy() -> bool {
-	match pg_client().await {
-		Ok(pg_client) => {
-			let rows = pg_client
-				.query(
-					"
-                        SELECT package.*, repository.bootstrap
-                        FROM package
-                        LEFT JOIN
-                            repository ON
-                            package.repository_id = repository.id
-                        WHERE
-                            package.visible = true
-                            AND latest_version = true
-                            AND package_id = ANY($1)
-                        ORDER BY
-                            quality ASC
-                    ",
-					&[&vec!["ws.hbang.common", "com.amywhile.aemulo"]],
-				)
-				.await;
+/// Orders package rows by repository preference for the given priority
+/// Rows need the `bootstrap` column of their repository joined in
+pub fn compare_priority(a: &Row, b: &Row, priority: &str) -> Ordering {
+	// If the priority is bootstrap, prioritize package.repository.is_bootstrap
+	if priority == "bootstrap" {
+		let a_bootstrap: bool = a.get("bootstrap");
+		let b_bootstrap: bool = b.get("bootstrap");
 
-			match rows {
-				Ok(_) => true,
-				Err(_) => false,
-			}
+		if a_bootstrap && !b_bootstrap {
+			return Ordering::Less;
+		} else if !a_bootstrap && b_bootstrap {
+			return Ordering::Greater;
 		}
-		Err(_) => false,
 	}
+
+	// If the priority is default, prioritize package.repository_tier
+	let a_quality: i32 = a.get("quality");
+	let b_quality: i32 = b.get("quality");
+	a_quality.cmp(&b_quality)
 }
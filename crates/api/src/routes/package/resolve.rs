@@ -0,0 +1,300 @@
+use crate::{
+	helpers::{
+		pg_client,
+		resolver::{resolve as resolve_plan, Candidate, Installed},
+		responses,
+	},
+	types::{InstallPlan, PackageRefs, PlannedPackage, ResolveFailure},
+	utility::{
+		client_architecture, download_url, installable_architectures, normalize_uri,
+		parse_relations, Relation,
+	},
+	AppState,
+};
+use anyhow::Result;
+use axum::{
+	extract::State,
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+	Json,
+};
+use deadpool_postgres::tokio_postgres::Row;
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing::error;
+
+/// Maximum number of packages that can be requested in a single resolution
+const MAX_REQUESTED_PACKAGES: usize = 100;
+
+/// Maximum number of times candidates are fetched for newly seen dependencies
+const MAX_FETCH_ROUNDS: usize = 16;
+
+#[derive(Debug, Deserialize)]
+pub struct InstalledVersion {
+	pub package_id: String,
+	pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveRequest {
+	/// Package IDs, optionally with a version constraint such as `pkg (>= 1.0)`
+	pub packages: Vec<String>,
+	/// IDs or URIs of the repositories enabled on the client
+	pub repositories: Vec<String>,
+	pub architecture: Option<String>,
+	#[serde(default)]
+	pub installed: Vec<InstalledVersion>,
+}
+
+/// Decodes a candidate from a row of the resolve query
+fn candidate(row: &Row) -> Result<Candidate> {
+	let relations = |field: &str| -> Result<Vec<Relation>> {
+		match row.try_get::<_, Option<String>>(field)? {
+			Some(value) => Ok(parse_relations(&value)),
+			None => Ok(Vec::new()),
+		}
+	};
+
+	let component: Option<String> = row.try_get("component")?;
+	Ok(Candidate {
+		id: row.try_get("id")?,
+		package_id: row.try_get("package_id")?,
+		version: row.try_get("version")?,
+		architecture: row.try_get("architecture")?,
+		repository_id: row.try_get("repository_id")?,
+		quality: row.try_get("quality")?,
+		package_filename: row.try_get("package_filename")?,
+		package_size: row.try_get("package_size")?,
+		sha256_hash: row.try_get("sha256_hash")?,
+		download_url: download_url(
+			row.try_get("uri")?,
+			row.try_get("suite")?,
+			component.as_deref(),
+			row.try_get("package_filename")?,
+		),
+		depends: relations("depends")?,
+		pre_depends: relations("pre_depends")?,
+		conflicts: relations("conflicts")?,
+		breaks: relations("breaks")?,
+		provides: relations("provides")?,
+	})
+}
+
+pub async fn resolve(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	body: Option<Json<ResolveRequest>>,
+) -> impl IntoResponse {
+	let body = match body {
+		Some(body) => body,
+		None => return responses::error(StatusCode::BAD_REQUEST, "Invalid request body"),
+	};
+
+	let requested = body
+		.packages
+		.iter()
+		.flat_map(|package| parse_relations(package))
+		.collect::<Vec<_>>();
+
+	if requested.is_empty() || requested.len() > MAX_REQUESTED_PACKAGES {
+		return responses::error(
+			StatusCode::BAD_REQUEST,
+			format!(
+				"Field \'packages\' must contain between 1 and {} packages",
+				MAX_REQUESTED_PACKAGES
+			),
+		);
+	}
+
+	if body.repositories.is_empty() {
+		return responses::error(
+			StatusCode::BAD_REQUEST,
+			"Field \'repositories\' must not be empty",
+		);
+	}
+
+	let architecture = match client_architecture(body.architecture.as_ref(), &headers) {
+		Ok(Some(architecture)) => architecture,
+		Ok(None) => {
+			return responses::error(StatusCode::BAD_REQUEST, "Missing field: \'architecture\'")
+		}
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = installable_architectures(&architecture);
+	let repositories = body
+		.repositories
+		.iter()
+		.map(|repository| normalize_uri(repository))
+		.collect::<Vec<String>>();
+
+	let pg_client = match pg_client(&state.pool).await {
+		Ok(pg_client) => pg_client,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	// Candidates are fetched in rounds until every dependency name has been seen
+	let mut candidates: Vec<Candidate> = Vec::new();
+	let mut seen: HashSet<String> = HashSet::new();
+	let mut names: Vec<String> = requested
+		.iter()
+		.flatten()
+		.map(|dependency| dependency.package.clone())
+		.collect();
+
+	for _ in 0..MAX_FETCH_ROUNDS {
+		names.retain(|name| seen.insert(name.clone()));
+		if names.is_empty() {
+			break;
+		}
+
+		let rows = match state
+			.metrics
+			.time_query(
+				"package_resolve",
+				pg_client.query(
+					"
+                    SELECT
+                        package.id,
+                        package.package_id,
+                        package.version,
+                        package.architecture,
+                        package.repository_id,
+                        package.quality,
+                        package.package_filename,
+                        package.package_size,
+                        package.sha256_hash,
+                        package.depends,
+                        package.pre_depends,
+                        package.conflicts,
+                        package.breaks,
+                        package.provides,
+                        repository.uri,
+                        repository.suite,
+                        repository.component
+                    FROM package
+                    INNER JOIN
+                        repository ON repository.id = package.repository_id
+                    WHERE
+                        package.visible = true
+                        AND repository.visible = true
+                        AND (
+                            repository.id = ANY($1)
+                            OR rtrim(regexp_replace(lower(repository.uri), '^https?://', ''), '/') = ANY($1)
+                        )
+                        AND (
+                            package.package_id = ANY($2)
+                            OR EXISTS (
+                                SELECT 1 FROM regexp_split_to_table(package.provides, ',') AS provided
+                                WHERE substring(provided FROM '^\\s*([^\\s(\\[<:]+)') = ANY($2)
+                            )
+                        )
+                        AND package.architecture = ANY($3)
+                ",
+					&[&repositories, &names, &architectures],
+				),
+			)
+			.await
+		{
+			Ok(rows) => rows,
+			Err(e) => {
+				error!(target: "db", "Failed to query database: {}", e);
+				return responses::error(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"Failed to query database",
+				);
+			}
+		};
+
+		let fetched = match rows
+			.iter()
+			.map(candidate)
+			.collect::<Result<Vec<Candidate>>>()
+		{
+			Ok(fetched) => fetched
+				.into_iter()
+				.filter(|fetched| {
+					!candidates
+						.iter()
+						.any(|candidate| candidate.id == fetched.id)
+				})
+				.collect::<Vec<Candidate>>(),
+			Err(e) => {
+				error!(target: "db", "Failed to decode package: {}", e);
+				return responses::error(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"Failed to query database",
+				);
+			}
+		};
+
+		names = fetched
+			.iter()
+			.flat_map(|candidate| candidate.dependency_names())
+			.filter(|name| !seen.contains(*name))
+			.map(|name| name.to_string())
+			.collect();
+
+		candidates.extend(fetched);
+	}
+
+	let installed = body
+		.installed
+		.iter()
+		.map(|package| Installed {
+			package_id: package.package_id.clone(),
+			version: package.version.clone(),
+		})
+		.collect::<Vec<Installed>>();
+
+	match resolve_plan(requested, &installed, &candidates) {
+		Ok(plan) => {
+			let packages = plan
+				.iter()
+				.map(|package| PlannedPackage {
+					id: package.id.clone(),
+					package_id: package.package_id.clone(),
+					version: package.version.clone(),
+					architecture: package.architecture.clone(),
+					repository_id: package.repository_id.clone(),
+					package_filename: package.package_filename.clone(),
+					package_size: package.package_size,
+					sha256_hash: package.sha256_hash.clone(),
+					download_url: package.download_url.clone(),
+					refs: PackageRefs {
+						meta: Some(format!(
+							"{}/jailbreak/package/{}",
+							state.config.api_endpoint, package.package_id
+						)),
+						repo: format!(
+							"{}/jailbreak/repository/{}",
+							state.config.api_endpoint, package.repository_id
+						),
+					},
+				})
+				.collect::<Vec<PlannedPackage>>();
+
+			responses::data(
+				StatusCode::OK,
+				InstallPlan {
+					count: packages.len(),
+					total_size: plan.iter().map(|package| package.package_size).sum(),
+					packages,
+				},
+			)
+		}
+		Err(unsatisfiable) => responses::error(
+			StatusCode::UNPROCESSABLE_ENTITY,
+			ResolveFailure {
+				message: "Unable to satisfy the requested packages".to_string(),
+				unsatisfiable,
+			},
+		),
+	}
+}
@@ -1,19 +1,67 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json, page_links},
+	helpers::{
+		responses::{self, PageRefs},
+		search::{
+			page_cursors, search_packages, split_list, Cursor, PackageFilters, PackageQuery,
+			PriceFilter, SearchEngine, SearchMode,
+		},
+	},
+	types::{FromRow, Package, PackageRefs, SearchPackage},
+	utility::{
+		client_architecture, compatible_architectures, cursor_links, installable_architectures,
+		package_relations, page_links_with_query,
+	},
+	AppState,
+};
+use axum::{
+	extract::{Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
 };
-use axum::{extract::Query, http::StatusCode, response::IntoResponse};
 use serde::Deserialize;
-use serde_json::{json, Value};
+use tracing::error;
 
 #[derive(Deserialize)]
 pub struct SearchParams {
 	q: Option<String>,
 	limit: Option<u8>,
 	page: Option<u8>,
+	cursor: Option<String>,
+	engine: Option<String>,
+	mode: Option<String>,
+	section: Option<String>,
+	architecture: Option<String>,
+	price: Option<String>,
+	repository: Option<String>,
+	author: Option<String>,
+	tags: Option<String>,
+	arch: Option<String>,
+}
+
+impl SearchParams {
+	/// Parameters carried over into pagination links
+	fn link_query(&self) -> Vec<(&'static str, Option<String>)> {
+		vec![
+			("q", self.q.clone()),
+			("limit", self.limit.map(|limit| limit.to_string())),
+			("engine", self.engine.clone()),
+			("mode", self.mode.clone()),
+			("section", self.section.clone()),
+			("architecture", self.architecture.clone()),
+			("price", self.price.clone()),
+			("repository", self.repository.clone()),
+			("author", self.author.clone()),
+			("tags", self.tags.clone()),
+			("arch", self.arch.clone()),
+		]
+	}
 }
 
-pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
+pub async fn search(
+	State(state): State<AppState>,
+	query: Query<SearchParams>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
 	let q = match &query.q {
 		Some(q) => {
 			if q.len() < 2 {
@@ -61,55 +109,120 @@ pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
 		None => 100,
 	};
 
-	let packages = match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				// Support our legacy fields
-				.query(
-					"
-					SELECT
-						package.*,
-						package.package_id AS package,
-						package.quality AS repositoryTier,
-						package.sileo_depiction AS sileoDepiction,
-						ts_rank(package.search_vector, plainto_tsquery('simple', $1)) AS rank,
-						(to_jsonb(repository) || jsonb_build_object(
-							'slug', repository.id,
-							'tier', repository.quality,
-							'isBootstrap', repository.bootstrap
-						)) AS repository
-					FROM package
-					RIGHT JOIN
-						repository ON repository.id = package.repository_id
-					WHERE
-						package.visible = true
-						AND latest_version = true
-						AND package.search_vector @@ plainto_tsquery('simple', $1)
-					ORDER BY
-						rank DESC,
-						package.quality ASC
-					LIMIT $2 OFFSET $3
-				",
-					&[
-						&q.to_string(),
-						&(limit as i64),
-						&(((page - 1) * limit) as i64),
-					],
-				)
-				.await
-			{
-				Ok(rows) => rows,
-				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
+	let mode = match &query.mode {
+		Some(mode) => match mode.parse::<SearchMode>() {
+			Ok(mode) => mode,
+			Err(_) => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'mode\' must be exact, prefix or fuzzy",
+				);
+			}
+		},
+
+		None => SearchMode::default(),
+	};
+
+	let engine = match &query.engine {
+		Some(engine) => match engine.parse::<SearchEngine>() {
+			Ok(engine) => engine,
+			Err(_) => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'engine\' must be postgres or typesense",
+				);
+			}
+		},
+
+		None => state.search_engine,
+	};
+
+	let cursor = match &query.cursor {
+		Some(token) => {
+			if engine != SearchEngine::Postgres {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					format!("Query parameter \'cursor\' is not supported by {}", engine),
+				);
+			}
+
+			match Cursor::decode(token, &state.caches.signing_key) {
+				Some(cursor) => Some(cursor),
+				None => {
 					return responses::error(
-						StatusCode::INTERNAL_SERVER_ERROR,
-						"Failed to query database",
+						StatusCode::BAD_REQUEST,
+						"Query parameter \'cursor\' is invalid",
 					);
 				}
 			}
 		}
+
+		None => None,
+	};
+
+	let price = match &query.price {
+		Some(price) => match price.parse::<PriceFilter>() {
+			Ok(price) => Some(price),
+			Err(_) => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'price\' must be free or paid",
+				);
+			}
+		},
+
+		None => None,
+	};
+
+	let installable = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(Some(architecture)) => installable_architectures(&architecture),
+		Ok(None) => Vec::new(),
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let filters = PackageFilters {
+		sections: split_list(&query.section),
+		architectures: split_list(&query.architecture),
+		price,
+		repositories: split_list(&query.repository),
+		authors: split_list(&query.author),
+		tags: split_list(&query.tags),
+		installable,
+	};
+
+	let results = match state
+		.metrics
+		.time_query(
+			"package_search",
+			search_packages(
+				&state,
+				engine,
+				&PackageQuery {
+					q,
+					limit,
+					page,
+					mode,
+					filters,
+					cursor: cursor.clone(),
+				},
+			),
+		)
+		.await
+	{
+		Ok(results) => results,
+		Err(e) => {
+			error!(target: "search", "Failed to query {}: {}", engine, e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query search engine",
+			);
+		}
+	};
+
+	let packages = match SearchPackage::from_rows(&results.rows) {
+		Ok(packages) => packages,
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to decode package: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -118,32 +231,39 @@ pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
 	};
 
 	let mut packages = packages
-		.iter()
-		.map(|row| {
-			let package_id: String = row.get("package_id");
-			let repository_id: String = row.get("repository_id");
-
-			// We need to attach repository to the package
-			let repository: Value = row.get("repository");
-			let mut value = row_to_value(row);
-			value["repository"] = repository;
-
-			return merge_json(
-				value,
-				json!({
-					"refs": {
-						"meta": format!("{}/jailbreak/package/{}", api_endpoint(), package_id),
-						"repo": format!("{}/jailbreak/repository/{}", api_endpoint(), repository_id),
-					}
-				}),
-			);
+		.into_iter()
+		.map(|package| {
+			let base = package.base;
+			SearchPackage {
+				base: Package {
+					compatible_architectures: Some(
+						compatible_architectures(&base.architecture)
+							.iter()
+							.map(|architecture| architecture.to_string())
+							.collect(),
+					),
+					relations: Some(package_relations(&base)),
+					refs: Some(PackageRefs {
+						meta: Some(format!(
+							"{}/jailbreak/package/{}",
+							state.config.api_endpoint, base.package_id
+						)),
+						repo: format!(
+							"{}/jailbreak/repository/{}",
+							state.config.api_endpoint, base.repository_id
+						),
+					}),
+					..base
+				},
+				..package
+			}
 		})
-		.collect::<Vec<Value>>();
+		.collect::<Vec<SearchPackage>>();
 
 	if packages.len() > 25 {
 		packages.sort_by(|a, b| {
-			let a = a["quality"].as_u64().unwrap_or(0);
-			let b = b["quality"].as_u64().unwrap_or(0);
+			let a = a.base.quality;
+			let b = b.base.quality;
 
 			if a < 4 && b >= 4 {
 				return std::cmp::Ordering::Less;
@@ -151,52 +271,49 @@ pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
 				return std::cmp::Ordering::Greater;
 			}
 
-			return std::cmp::Ordering::Equal;
+			std::cmp::Ordering::Equal
 		});
 	}
 
-	let next = packages.len() == limit as usize;
-	let (prev_page, next_page) = page_links("/jailbreak/package/search", page, next);
+	// Typesense paginates by page number, so only ranked rows get cursors
+	let (prev_page, next_page) = match engine {
+		SearchEngine::Postgres => {
+			let (prev_cursor, next_cursor) = page_cursors(
+				&state.caches.signing_key,
+				&results.rows,
+				cursor.as_ref(),
+				page,
+				results.has_more,
+			);
+
+			cursor_links(
+				&state.config.api_endpoint,
+				"/jailbreak/package/search",
+				&query.link_query(),
+				prev_cursor,
+				next_cursor,
+			)
+		}
+
+		SearchEngine::Typesense => page_links_with_query(
+			&state.config.api_endpoint,
+			"/jailbreak/package/search",
+			&query.link_query(),
+			page,
+			results.has_more,
+		),
+	};
 
-	responses::data_with_count_and_refs(
+	responses::data_with_total_refs_and_facets(
 		StatusCode::OK,
 		&packages,
 		packages.len(),
-		json!({
-			"nextPage": next_page,
-			"previousPage": prev_page,
-		}),
+		results.total,
+		next_page.is_some(),
+		PageRefs {
+			nextPage: next_page,
+			previousPage: prev_page,
+		},
+		results.facets,
 	)
 }
-
-pub async fn search_healthy() -> bool {
-	match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-						SELECT *, ts_rank(
-							search_vector,
-							plainto_tsquery('simple', 'crane')
-						) AS rank
-						FROM package
-						WHERE
-							visible = true
-							AND latest_version = true
-							AND search_vector @@ plainto_tsquery('simple', 'crane')
-						ORDER BY
-							rank DESC,
-							quality ASC
-						LIMIT 1 OFFSET 0
-                    ",
-					&[],
-				)
-				.await
-			{
-				Ok(_) => true,
-				Err(_) => false,
-			}
-		}
-		Err(_) => false,
-	}
-}
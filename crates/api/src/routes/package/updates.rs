@@ -0,0 +1,203 @@
+use super::compare_priority;
+use crate::{
+	helpers::{pg_client, responses},
+	types::{HashChange, PackageUpdate, SizeChange, UpdateChanges, UpdateRefs},
+	utility::{client_architecture, compare_versions, installable_architectures, normalize_uri},
+	AppState,
+};
+use axum::{
+	extract::{Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+	Json,
+};
+use deadpool_postgres::tokio_postgres::Row;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use tracing::error;
+
+/// Maximum number of installed packages accepted in a single update check
+const MAX_INSTALLED_PACKAGES: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct UpdatesParams {
+	priority: Option<String>,
+	arch: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstalledPackage {
+	pub package_id: String,
+	pub version: String,
+	pub repository_uri: String,
+}
+
+pub async fn updates(
+	State(state): State<AppState>,
+	query: Query<UpdatesParams>,
+	headers: HeaderMap,
+	body: Option<Json<Vec<InstalledPackage>>>,
+) -> impl IntoResponse {
+	let installed = match body {
+		Some(body) if !body.is_empty() => body,
+		_ => return responses::error(StatusCode::BAD_REQUEST, "Invalid request body"),
+	};
+
+	if installed.len() > MAX_INSTALLED_PACKAGES {
+		return responses::error(
+			StatusCode::BAD_REQUEST,
+			format!(
+				"Request body must contain at most {} packages",
+				MAX_INSTALLED_PACKAGES
+			),
+		);
+	}
+
+	let priority = match &query.priority {
+		Some(priority) => priority,
+		None => "default",
+	};
+
+	let architecture = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(architecture) => architecture,
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = architecture.as_deref().map(installable_architectures);
+
+	let ids: Vec<&str> = installed
+		.iter()
+		.map(|package| package.package_id.as_str())
+		.collect();
+
+	let versions: Vec<&str> = installed
+		.iter()
+		.map(|package| package.version.as_str())
+		.collect();
+
+	// Installed versions are fetched alongside the latest ones to compare sizes and hashes
+	let mut packages = match pg_client(&state.pool).await {
+		Ok(pg_client) => {
+			match state
+				.metrics
+				.time_query(
+					"package_updates",
+					pg_client.query(
+						"
+                        SELECT package.*, repository.bootstrap, repository.uri AS repository_uri
+                        FROM package
+                        INNER JOIN
+                            repository ON
+                            package.repository_id = repository.id
+                        WHERE
+                            package.visible = true
+                            AND package.package_id = ANY($1)
+                            AND (package.latest_version = true OR package.version = ANY($2))
+                            AND ($3::text[] IS NULL OR package.architecture = ANY($3))
+                        ORDER BY
+                            package.quality ASC
+                    ",
+						&[&ids, &versions, &architectures],
+					),
+				)
+				.await
+			{
+				Ok(rows) => rows,
+				Err(e) => {
+					error!(target: "db", "Failed to query database: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to query database",
+					);
+				}
+			}
+		}
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	packages.sort_by(|a, b| compare_priority(a, b, priority));
+
+	let updates = installed
+		.iter()
+		.filter_map(|package| {
+			let repository_uri = normalize_uri(&package.repository_uri);
+			let from_repository =
+				|row: &Row| normalize_uri(row.get::<_, &str>("repository_uri")) == repository_uri;
+
+			let rows: Vec<&Row> = packages
+				.iter()
+				.filter(|row| row.get::<_, &str>("package_id") == package.package_id)
+				.collect();
+
+			// Stay on the repository the package was installed from when it still has it
+			let latest: Vec<&Row> = rows
+				.iter()
+				.copied()
+				.filter(|row| row.get::<_, bool>("latest_version"))
+				.collect();
+
+			let candidate = latest
+				.iter()
+				.find(|row| from_repository(row))
+				.or_else(|| latest.first())?;
+
+			let candidate_version: &str = candidate.get("version");
+			if compare_versions(candidate_version, &package.version) != Ordering::Greater {
+				return None;
+			}
+
+			let current = rows
+				.iter()
+				.filter(|row| row.get::<_, &str>("version") == package.version)
+				.find(|row| from_repository(row))
+				.copied();
+
+			let current_size = current.map(|row| row.get::<_, i64>("package_size"));
+			let current_hash = current.and_then(|row| row.get::<_, Option<String>>("sha256_hash"));
+			let size: i64 = candidate.get("package_size");
+			let hash: Option<String> = candidate.get("sha256_hash");
+			let repository_id: String = candidate.get("repository_id");
+
+			Some(PackageUpdate {
+				package_id: package.package_id.clone(),
+				installed_version: package.version.clone(),
+				version: candidate_version.to_string(),
+				id: candidate.get("id"),
+				architecture: candidate.get("architecture"),
+				package_filename: candidate.get("package_filename"),
+				repository_uri: candidate.get("repository_uri"),
+				repository_changed: !from_repository(candidate),
+				changes: UpdateChanges {
+					package_size: SizeChange {
+						from: current_size,
+						to: size,
+						delta: current_size.map(|current_size| size - current_size),
+					},
+					sha256_hash: HashChange {
+						from: current_hash,
+						to: hash,
+					},
+				},
+				refs: UpdateRefs {
+					package: format!(
+						"{}/jailbreak/package/{}",
+						state.config.api_endpoint, package.package_id
+					),
+					repo: format!(
+						"{}/jailbreak/repository/{}",
+						state.config.api_endpoint, repository_id
+					),
+				},
+				repository_id,
+			})
+		})
+		.collect::<Vec<PackageUpdate>>();
+
+	responses::data_with_count(StatusCode::OK, &updates, updates.len())
+}
@@ -0,0 +1,137 @@
+use crate::{
+	helpers::{pg_client, responses},
+	types::{FromRow, PackageRefs, PackageVersion, RepositoryVersions},
+	utility::{client_architecture, compare_versions, installable_architectures},
+	AppState,
+};
+use anyhow::Result;
+use axum::{
+	extract::{Path, Query, State},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+};
+use deadpool_postgres::tokio_postgres::Row;
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Deserialize)]
+pub struct VersionsParams {
+	arch: Option<String>,
+}
+
+pub async fn versions(
+	State(state): State<AppState>,
+	package: Path<String>,
+	query: Query<VersionsParams>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	let architecture = match client_architecture(query.arch.as_ref(), &headers) {
+		Ok(architecture) => architecture,
+		Err(message) => return responses::error(StatusCode::BAD_REQUEST, message),
+	};
+
+	let architectures = architecture.as_deref().map(installable_architectures);
+
+	let rows = match pg_client(&state.pool).await {
+		Ok(pg_client) => {
+			match state
+				.metrics
+				.time_query(
+					"package_versions",
+					pg_client.query(
+						"
+                        SELECT
+                            package.id,
+                            package.version,
+                            package.architecture,
+                            package.latest_version,
+                            package.package_filename,
+                            package.package_size,
+                            package.sha256_hash,
+                            package.repository_id,
+                            repository.quality
+                        FROM package
+                        INNER JOIN
+                            repository ON repository.id = package.repository_id
+                        WHERE
+                            package.visible = true
+                            AND package.package_id = $1
+                            AND ($2::text[] IS NULL OR package.architecture = ANY($2))
+                        ORDER BY
+                            repository.quality ASC,
+                            repository.id ASC
+                    ",
+						&[&package.to_string(), &architectures],
+					),
+				)
+				.await
+			{
+				Ok(rows) => rows,
+				Err(e) => {
+					error!(target: "db", "Failed to query database: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to query database",
+					);
+				}
+			}
+		}
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	if rows.is_empty() {
+		return responses::error(StatusCode::NOT_FOUND, "Package not found");
+	}
+
+	let history = match version_history(&rows, &state.config.api_endpoint) {
+		Ok(history) => history,
+		Err(e) => {
+			error!(target: "db", "Failed to decode package version: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	responses::data_with_count(StatusCode::OK, &history, history.len())
+}
+
+/// Groups the rows of the versions query by repository, newest version first
+/// Rows arrive grouped by repository in order of quality
+fn version_history(rows: &[Row], api_endpoint: &str) -> Result<Vec<RepositoryVersions>> {
+	let mut history: Vec<RepositoryVersions> = Vec::new();
+	for row in rows {
+		let repository_id: String = row.try_get("repository_id")?;
+		let version = PackageVersion::from_row(row)?;
+
+		match history.last_mut() {
+			Some(repository) if repository.repository_id == repository_id => {
+				repository.versions.push(version)
+			}
+			_ => history.push(RepositoryVersions {
+				refs: PackageRefs {
+					meta: None,
+					repo: format!("{}/jailbreak/repository/{}", api_endpoint, repository_id),
+				},
+				repository_id,
+				quality: row.try_get("quality")?,
+				versions: vec![version],
+			}),
+		}
+	}
+
+	for repository in &mut history {
+		repository
+			.versions
+			.sort_by(|a, b| compare_versions(&b.version, &a.version));
+	}
+
+	Ok(history)
+}
@@ -0,0 +1,228 @@
+use crate::helpers::{
+	apt::{index_path, release_file, AptIndices, Compression, APT_COMPONENT, APT_SUITE},
+	pg_client, responses,
+};
+use crate::{
+	types::{FromRow, Package, Repository},
+	AppState,
+};
+use axum::{
+	extract::{Path, State},
+	http::{header, StatusCode},
+	response::{IntoResponse, Response},
+};
+use std::{sync::Arc, time::Instant};
+use tracing::error;
+
+/// Number of repositories whose indices are kept in memory
+const MAX_CACHED_INDICES: usize = 64;
+
+/// Files that can be requested below `dists/`
+enum DistsFile {
+	Release,
+	Packages(String, Compression),
+}
+
+impl DistsFile {
+	fn parse(path: &str) -> Option<Self> {
+		let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+
+		match segments.as_slice() {
+			[APT_SUITE, "Release"] => Some(DistsFile::Release),
+			[APT_SUITE, APT_COMPONENT, binary, file_name] => {
+				let architecture = binary.strip_prefix("binary-")?;
+				let compression = Compression::from_file_name(file_name)?;
+				Some(DistsFile::Packages(architecture.to_string(), compression))
+			}
+			_ => None,
+		}
+	}
+}
+
+/// Returns the cached indices of a repository unless it has been indexed since
+async fn cached_indices(state: &AppState, id: &str, last_updated: &str) -> Option<Arc<AptIndices>> {
+	let cache = state.caches.apt_indices.lock().await;
+	match cache.get(id) {
+		Some((_, indices)) if indices.last_updated == last_updated => Some(indices.clone()),
+		_ => None,
+	}
+}
+
+/// Caches the indices of a repository, evicting the oldest build when full
+async fn cache_indices(state: &AppState, id: String, indices: Arc<AptIndices>) {
+	let mut cache = state.caches.apt_indices.lock().await;
+	if cache.len() >= MAX_CACHED_INDICES && !cache.contains_key(&id) {
+		let oldest = cache
+			.iter()
+			.min_by_key(|(_, (built, _))| *built)
+			.map(|(id, _)| id.clone());
+
+		if let Some(oldest) = oldest {
+			cache.remove(&oldest);
+		}
+	}
+
+	cache.insert(id, (Instant::now(), indices));
+}
+
+pub async fn dists(
+	State(state): State<AppState>,
+	Path((id, path)): Path<(String, String)>,
+) -> Response {
+	let file = match DistsFile::parse(&path) {
+		Some(file) => file,
+		None => return responses::error(StatusCode::NOT_FOUND, "File not found").into_response(),
+	};
+
+	let pg_client = match pg_client(&state.pool).await {
+		Ok(pg_client) => pg_client,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+	};
+
+	let repository = match state
+		.metrics
+		.time_query(
+			"repository_dists",
+			pg_client.query(
+				"
+                SELECT * FROM repository
+                WHERE
+                    visible = true
+                    AND id = $1
+                LIMIT 1
+            ",
+				&[&id],
+			),
+		)
+		.await
+	{
+		Ok(rows) => rows,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+	};
+
+	let repository = match repository.first().map(Repository::from_row) {
+		Some(Ok(repository)) => repository,
+		Some(Err(e)) => {
+			error!(target: "db", "Failed to decode repository: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+		None => {
+			return responses::error(StatusCode::NOT_FOUND, "Repository not found").into_response()
+		}
+	};
+
+	let indices = match cached_indices(&state, &id, &repository.origin_last_updated).await {
+		Some(indices) => indices,
+		None => {
+			let packages = match state
+				.metrics
+				.time_query(
+					"repository_dists",
+					pg_client.query(
+						"
+                        SELECT * FROM package
+                        WHERE
+                            visible = true
+                            AND repository_id = $1
+                        ORDER BY
+                            package_id ASC,
+                            id ASC
+                    ",
+						&[&id],
+					),
+				)
+				.await
+			{
+				Ok(rows) => rows,
+				Err(e) => {
+					error!(target: "db", "Failed to query database: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to query database",
+					)
+					.into_response();
+				}
+			};
+
+			let packages = match Package::from_rows(&packages) {
+				Ok(packages) => packages,
+				Err(e) => {
+					error!(target: "db", "Failed to decode package: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to query database",
+					)
+					.into_response();
+				}
+			};
+
+			let upstream = repository.clone();
+			let built =
+				tokio::task::spawn_blocking(move || AptIndices::build(&packages, &upstream)).await;
+
+			let indices = match built {
+				Ok(Ok(indices)) => Arc::new(indices),
+				Ok(Err(e)) => {
+					error!(target: "apt", "Failed to compress index: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to build index",
+					)
+					.into_response();
+				}
+				Err(e) => {
+					error!(target: "apt", "Failed to build index: {}", e);
+					return responses::error(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						"Failed to build index",
+					)
+					.into_response();
+				}
+			};
+
+			cache_indices(&state, id.clone(), indices.clone()).await;
+			indices
+		}
+	};
+
+	let (compression, body) = match file {
+		DistsFile::Release => {
+			let release = release_file(&repository, &indices.architectures, &indices.files);
+			(Compression::None, release.into_bytes())
+		}
+		DistsFile::Packages(architecture, compression) => {
+			match indices.file(&index_path(&architecture, compression)) {
+				Some(contents) => (compression, contents.to_vec()),
+				None => {
+					return responses::error(StatusCode::NOT_FOUND, "Architecture not found")
+						.into_response()
+				}
+			}
+		}
+	};
+
+	(
+		StatusCode::OK,
+		[(header::CONTENT_TYPE, compression.content_type())],
+		body,
+	)
+		.into_response()
+}
@@ -0,0 +1,90 @@
+use crate::{
+	helpers::{pg_client, responses},
+	types::{FromRow, Repository},
+	utility::download_url,
+	AppState,
+};
+use axum::{
+	extract::{Path, State},
+	http::{header, StatusCode},
+	response::{IntoResponse, Response},
+};
+use tracing::error;
+
+/// Redirects a `Filename` from the synthesized APT indices to the upstream
+/// repository, since the packages themselves aren't mirrored
+pub async fn file(
+	State(state): State<AppState>,
+	Path((id, filename)): Path<(String, String)>,
+) -> Response {
+	let pg_client = match pg_client(&state.pool).await {
+		Ok(pg_client) => pg_client,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+	};
+
+	let rows = match state
+		.metrics
+		.time_query(
+			"repository_file",
+			pg_client.query(
+				"
+                SELECT repository.*, package.package_filename FROM package
+                JOIN repository ON repository.id = package.repository_id
+                WHERE
+                    package.visible = true
+                    AND repository.visible = true
+                    AND package.repository_id = $1
+                    AND regexp_replace(package.package_filename, '^(\\./)*/*', '') = $2
+                LIMIT 1
+            ",
+				&[&id, &filename.trim_start_matches('/')],
+			),
+		)
+		.await
+	{
+		Ok(rows) => rows,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+	};
+
+	let row = match rows.first() {
+		Some(row) => row,
+		None => return responses::error(StatusCode::NOT_FOUND, "File not found").into_response(),
+	};
+
+	let (repository, package_filename) = match Repository::from_row(row)
+		.and_then(|repository| Ok((repository, row.try_get::<_, String>("package_filename")?)))
+	{
+		Ok(decoded) => decoded,
+		Err(e) => {
+			error!(target: "db", "Failed to decode package: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			)
+			.into_response();
+		}
+	};
+
+	let location = download_url(
+		&repository.uri,
+		&repository.suite,
+		repository.component.as_deref(),
+		&package_filename,
+	);
+
+	(StatusCode::FOUND, [(header::LOCATION, location)]).into_response()
+}
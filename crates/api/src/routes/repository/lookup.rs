@@ -1,29 +1,38 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json},
+	helpers::{pg_client, responses},
+	types::{FromRow, Repository, RepositoryRefs},
+	AppState,
 };
-use axum::{extract::Path, http::StatusCode, response::IntoResponse};
-use serde_json::json;
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use tracing::error;
 
-pub async fn lookup(id: Path<String>) -> impl IntoResponse {
-	let repository = match pg_client().await {
+pub async fn lookup(State(state): State<AppState>, id: Path<String>) -> impl IntoResponse {
+	let rows = match pg_client(&state.pool).await {
 		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
+			match state
+				.metrics
+				.time_query(
+					"repository_lookup",
+					pg_client.query(
+						"
                         SELECT * FROM repository
                         WHERE
                             visible = true
                             AND id = $1
                         LIMIT 1
                     ",
-					&[&id.to_string()],
+						&[&id.to_string()],
+					),
 				)
 				.await
 			{
 				Ok(rows) => rows,
 				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
+					error!(target: "db", "Failed to query database: {}", e);
 					return responses::error(
 						StatusCode::INTERNAL_SERVER_ERROR,
 						"Failed to query database",
@@ -32,7 +41,7 @@ pub async fn lookup(id: Path<String>) -> impl IntoResponse {
 			}
 		}
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to query database: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -40,44 +49,32 @@ pub async fn lookup(id: Path<String>) -> impl IntoResponse {
 		}
 	};
 
-	if repository.len() == 0 {
-		return responses::error(StatusCode::NOT_FOUND, "Repository not found");
-	}
+	let row = match rows.first() {
+		Some(row) => row,
+		None => return responses::error(StatusCode::NOT_FOUND, "Repository not found"),
+	};
 
-	let row = &repository[0];
-	let id: String = row.get("id");
-	let repository = merge_json(
-		row_to_value(&row),
-		json!({
-			"refs": {
-				"packages": format!("{}/jailbreak/repository/{}/packages", api_endpoint(), id),
-			}
+	let repository = match Repository::from_row(row) {
+		Ok(repository) => repository,
+		Err(e) => {
+			error!(target: "db", "Failed to decode repository: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let repository = Repository {
+		refs: Some(RepositoryRefs {
+			meta: None,
+			packages: format!(
+				"{}/jailbreak/repository/{}/packages",
+				state.config.api_endpoint, repository.id
+			),
 		}),
-	);
+		..repository
+	};
 
 	responses::data(StatusCode::OK, repository)
 }
-
-pub async fn lookup_healthy() -> bool {
-	match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-                        SELECT * FROM repository
-                        WHERE
-                            visible = true
-                            AND id = 'chariz'
-                        LIMIT 1
-                    ",
-					&[],
-				)
-				.await
-			{
-				Ok(_) => true,
-				Err(_) => false,
-			}
-		}
-		Err(_) => false,
-	}
-}
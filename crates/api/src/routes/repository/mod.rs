@@ -1,9 +1,13 @@
+mod dists;
+mod file;
 mod lookup;
 mod packages;
 mod ranking;
 mod safety;
 mod search;
 
+pub use self::dists::*;
+pub use self::file::*;
 pub use self::lookup::*;
 pub use self::packages::*;
 pub use self::ranking::*;
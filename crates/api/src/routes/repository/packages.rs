@@ -1,38 +1,233 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json},
+	helpers::{
+		pg_client,
+		responses::{self, PageRefs},
+	},
+	types::{FromRow, Package, PackageRefs},
+	utility::{package_relations, page_links_with_query},
+	AppState,
 };
-use axum::{extract::Path, http::StatusCode, response::IntoResponse};
-use serde_json::{json, Value};
-
-pub async fn packages(id: Path<String>) -> impl IntoResponse {
-	let repository = match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-                        SELECT * FROM repository
-                        WHERE
-                            visible = true
-                            AND id = $1
-                        LIMIT 1
-                    ",
-					&[&id.to_string()],
-				)
-				.await
-			{
-				Ok(rows) => rows,
-				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
-					return responses::error(
-						StatusCode::INTERNAL_SERVER_ERROR,
-						"Failed to query database",
-					);
-				}
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::{to_value, Map, Value};
+use std::str::FromStr;
+use tracing::error;
+
+#[derive(Deserialize)]
+pub struct PackagesParams {
+	limit: Option<u8>,
+	page: Option<u8>,
+	sort: Option<String>,
+	latest_only: Option<bool>,
+	fields: Option<String>,
+}
+
+/// Orderings available for a repository's package listing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PackageSort {
+	#[default]
+	Name,
+	Updated,
+	Size,
+	Section,
+}
+
+/// Values accepted by `sort`, listed when an unknown one is given
+const PACKAGE_SORTS: [&str; 4] = ["name", "updated", "size", "section"];
+
+impl FromStr for PackageSort {
+	type Err = ();
+
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		match value.to_ascii_lowercase().as_str() {
+			"name" => Ok(PackageSort::Name),
+			"updated" => Ok(PackageSort::Updated),
+			"size" => Ok(PackageSort::Size),
+			"section" => Ok(PackageSort::Section),
+			_ => Err(()),
+		}
+	}
+}
+
+impl PackageSort {
+	/// ORDER BY clause of the sort, every ordering ends on the unique id so
+	/// that pages never overlap
+	fn order_by(&self) -> &'static str {
+		match self {
+			PackageSort::Name => "lower(coalesce(name, package_id)) ASC, package_id ASC, id ASC",
+			// The indexer only tracks when a whole repository was last updated, so
+			// every package in the listing ties on it and the identifiers decide
+			PackageSort::Updated => "package_id ASC, id ASC",
+			PackageSort::Size => "package_size DESC, package_id ASC, id ASC",
+			PackageSort::Section => {
+				"section ASC NULLS LAST, lower(coalesce(name, package_id)) ASC, package_id ASC, id ASC"
 			}
 		}
+	}
+}
+
+/// Top-level fields of a listed package that can be selected with `fields`
+const PACKAGE_FIELDS: [&str; 33] = [
+	"id",
+	"package_id",
+	"latest_version",
+	"visible",
+	"quality",
+	"repository_id",
+	"price",
+	"version",
+	"architecture",
+	"package_filename",
+	"package_size",
+	"sha256_hash",
+	"name",
+	"description",
+	"author",
+	"maintainer",
+	"depiction",
+	"native_depiction",
+	"sileo_depiction",
+	"header_url",
+	"tint_color",
+	"icon_url",
+	"section",
+	"tags",
+	"installed_size",
+	"depends",
+	"pre_depends",
+	"conflicts",
+	"breaks",
+	"provides",
+	"replaces",
+	"relations",
+	"refs",
+];
+
+/// Keeps only the requested top-level fields of a package object
+fn project(value: Value, fields: &[String]) -> Value {
+	match value {
+		Value::Object(object) => Value::Object(
+			object
+				.into_iter()
+				.filter(|(key, _)| fields.contains(key))
+				.collect::<Map<String, Value>>(),
+		),
+		value => value,
+	}
+}
+
+pub async fn packages(
+	State(state): State<AppState>,
+	id: Path<String>,
+	query: Query<PackagesParams>,
+) -> impl IntoResponse {
+	let page = match query.page {
+		Some(page) => {
+			if page < 1 {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'page\' must be greater than 0",
+				);
+			}
+
+			page
+		}
+
+		None => 1,
+	};
+
+	let limit = match query.limit {
+		Some(limit) => {
+			if !(1..=250).contains(&limit) {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'limit\' must be between 1 and 250",
+				);
+			}
+
+			limit
+		}
+
+		None => 100,
+	};
+
+	let sort = match &query.sort {
+		Some(sort) => match sort.parse::<PackageSort>() {
+			Ok(sort) => sort,
+			Err(_) => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					format!(
+						"Query parameter \'sort\' must be one of {}",
+						PACKAGE_SORTS.join(", ")
+					),
+				);
+			}
+		},
+
+		None => PackageSort::default(),
+	};
+
+	let fields = query.fields.as_ref().map(|fields| {
+		fields
+			.split(',')
+			.map(|field| field.trim().to_string())
+			.filter(|field| !field.is_empty())
+			.collect::<Vec<String>>()
+	});
+
+	let unknown = fields
+		.iter()
+		.flatten()
+		.find(|field| !PACKAGE_FIELDS.contains(&field.as_str()));
+
+	if let Some(field) = unknown {
+		return responses::error(
+			StatusCode::BAD_REQUEST,
+			format!(
+				"Query parameter \'fields\' contains an unknown field: {}",
+				field
+			),
+		);
+	}
+
+	let latest_only = query.latest_only.unwrap_or(false);
+
+	let pg_client = match pg_client(&state.pool).await {
+		Ok(pg_client) => pg_client,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let repository = match state
+		.metrics
+		.time_query(
+			"repository_packages",
+			pg_client.query(
+				"
+                SELECT * FROM repository
+                WHERE
+                    visible = true
+                    AND id = $1
+                LIMIT 1
+            ",
+				&[&id.to_string()],
+			),
+		)
+		.await
+	{
+		Ok(rows) => rows,
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to query database: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -40,39 +235,59 @@ pub async fn packages(id: Path<String>) -> impl IntoResponse {
 		}
 	};
 
-	if repository.len() == 0 {
+	if repository.is_empty() {
 		return responses::error(StatusCode::NOT_FOUND, "Repository not found");
 	}
 
 	let row = &repository[0];
 	let id: String = row.get("id");
 
-	let packages = match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
+	// One extra row is fetched to tell if there is a next page
+	let mut rows = match state
+		.metrics
+		.time_query(
+			"repository_packages",
+			pg_client.query(
+				&format!(
 					"
-                        SELECT * FROM package
-                        WHERE
-                            visible = true
-                            AND repository_id = $1
-                    ",
-					&[&id.to_string()],
-				)
-				.await
-			{
-				Ok(rows) => rows,
-				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
-					return responses::error(
-						StatusCode::INTERNAL_SERVER_ERROR,
-						"Failed to query database",
-					);
-				}
-			}
+                    SELECT * FROM package
+                    WHERE
+                        visible = true
+                        AND repository_id = $1
+                        AND ($2 = false OR latest_version = true)
+                    ORDER BY {}
+                    LIMIT $3
+                    OFFSET $4
+                ",
+					sort.order_by()
+				),
+				&[
+					&id.to_string(),
+					&latest_only,
+					&(limit as i64 + 1),
+					&((page as i64 - 1) * limit as i64),
+				],
+			),
+		)
+		.await
+	{
+		Ok(rows) => rows,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
 		}
+	};
+
+	let next = rows.len() > limit as usize;
+	rows.truncate(limit as usize);
+
+	let packages = match Package::from_rows(&rows) {
+		Ok(packages) => packages,
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to decode package: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -80,74 +295,127 @@ pub async fn packages(id: Path<String>) -> impl IntoResponse {
 		}
 	};
 
-	responses::data_with_count(
-		StatusCode::OK,
-		packages
-			.iter()
-			.map(|row| {
-				let package_id: String = row.get("package_id");
-
-				merge_json(
-					row_to_value(&row),
-					json!({
-						"refs": {
-							"meta": format!("{}/jailbreak/package/{}", api_endpoint(), package_id),
-							"repo": format!("{}/jailbreak/repository/{}", api_endpoint(), id),
-						}
-					}),
-				)
-			})
+	let packages = packages
+		.into_iter()
+		.map(|package| {
+			let package = Package {
+				relations: Some(package_relations(&package)),
+				refs: Some(PackageRefs {
+					meta: Some(format!(
+						"{}/jailbreak/package/{}",
+						state.config.api_endpoint, package.package_id
+					)),
+					repo: format!("{}/jailbreak/repository/{}", state.config.api_endpoint, id),
+				}),
+				..package
+			};
+
+			to_value(package).unwrap_or_default()
+		})
+		.collect::<Vec<Value>>();
+
+	let packages = match &fields {
+		Some(fields) => packages
+			.into_iter()
+			.map(|package| project(package, fields))
 			.collect::<Vec<Value>>(),
+		None => packages,
+	};
+
+	let (prev_page, next_page) = page_links_with_query(
+		&state.config.api_endpoint,
+		&format!("/jailbreak/repository/{}/packages", id),
+		&[
+			("limit", query.limit.map(|limit| limit.to_string())),
+			("sort", query.sort.clone()),
+			(
+				"latest_only",
+				query.latest_only.map(|latest| latest.to_string()),
+			),
+			("fields", query.fields.clone()),
+		],
+		page,
+		next,
+	);
+
+	responses::data_with_count_and_refs(
+		StatusCode::OK,
+		&packages,
 		packages.len(),
+		PageRefs {
+			nextPage: next_page,
+			previousPage: prev_page,
+		},
 	)
 }
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
 
-pub async fn packages_healthy() -> bool {
-	let repository = match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-                        SELECT * FROM repository
-                        WHERE
-                            visible = true
-                            AND id = 'chariz'
-                        LIMIT 1
-                    ",
-					&[],
-				)
-				.await
-			{
-				Ok(rows) => rows,
-				Err(_) => return false,
-			}
+	#[test]
+	fn sort_parses_known_values() {
+		assert_eq!("Size".parse::<PackageSort>(), Ok(PackageSort::Size));
+		assert!("newest".parse::<PackageSort>().is_err());
+
+		for sort in PACKAGE_SORTS {
+			assert!(sort.parse::<PackageSort>().is_ok());
 		}
-		Err(_) => return false,
-	};
+	}
+
+	#[test]
+	fn selectable_fields_match_the_package_model() {
+		let package: Package = serde_json::from_value(json!({
+			"id": "c3",
+			"package_id": "ws.hbang.common",
+			"latest_version": true,
+			"visible": true,
+			"quality": 1,
+			"repository_id": "chariz",
+			"price": "Free",
+			"version": "2.0",
+			"architecture": "iphoneos-arm64",
+			"package_filename": "./debs/common_2.0.deb",
+			"package_size": 200
+		}))
+		.unwrap();
+
+		let package = Package {
+			relations: Some(package_relations(&package)),
+			refs: Some(PackageRefs {
+				meta: None,
+				repo: "https://api.canister.me/v2/jailbreak/repository/chariz".to_string(),
+			}),
+			..package
+		};
+
+		let mut serialized = to_value(package)
+			.unwrap()
+			.as_object()
+			.unwrap()
+			.keys()
+			.cloned()
+			.collect::<Vec<String>>();
 
-	if repository.len() == 0 {
-		return false;
+		let mut fields = PACKAGE_FIELDS.map(|field| field.to_string()).to_vec();
+		serialized.sort();
+		fields.sort();
+		assert_eq!(serialized, fields);
 	}
 
-	match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-                        SELECT * FROM package
-                        WHERE
-                            visible = true
-                            AND repository_id = 'chariz'
-                        LIMIT 1000
-                    ",
-					&[],
-				)
-				.await
-			{
-				Ok(_) => true,
-				Err(_) => false,
-			}
-		}
-		Err(_) => false,
+	#[test]
+	fn projection_keeps_requested_fields() {
+		let package = json!({
+			"package_id": "ws.hbang.common",
+			"version": "2.0",
+			"name": "Cephei",
+			"section": "System",
+		});
+
+		let fields = vec!["package_id".to_string(), "name".to_string()];
+		assert_eq!(
+			project(package, &fields),
+			json!({ "package_id": "ws.hbang.common", "name": "Cephei" })
+		);
 	}
 }
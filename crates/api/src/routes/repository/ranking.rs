@@ -1,17 +1,26 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, handle_error, merge_json},
+	helpers::{pg_client, responses},
+	types::{FromRow, Repository, RepositoryRefs},
+	utility::handle_error,
+	AppState,
+};
+use axum::{
+	extract::{Query, State},
+	http::StatusCode,
+	response::IntoResponse,
 };
-use axum::{extract::Query, http::StatusCode, response::IntoResponse};
 use serde::Deserialize;
-use serde_json::{json, Value};
+use tracing::error;
 
 #[derive(Deserialize)]
 pub struct RankingParams {
 	rank: Option<String>,
 }
 
-pub async fn ranking(query: Query<RankingParams>) -> impl IntoResponse {
+pub async fn ranking(
+	State(state): State<AppState>,
+	query: Query<RankingParams>,
+) -> impl IntoResponse {
 	let rank = match &query.rank {
 		Some(q) => {
 			let match_q = match q.as_str() {
@@ -38,21 +47,25 @@ pub async fn ranking(query: Query<RankingParams>) -> impl IntoResponse {
 	};
 
 	let lookup = match rank.as_str() {
-		"*" => match pg_client().await {
+		"*" => match pg_client(&state.pool).await {
 			Ok(pg_client) => {
-				pg_client
-					.query(
-						"
+				state
+					.metrics
+					.time_query(
+						"repository_ranking",
+						pg_client.query(
+							"
                             SELECT * FROM repository
                             WHERE visible = true
                             ORDER BY quality ASC
                         ",
-						&[],
+							&[],
+						),
 					)
 					.await
 			}
 			Err(e) => {
-				eprintln!("[db] Failed to query database: {}", e);
+				error!(target: "db", "Failed to query database: {}", e);
 				return responses::error(
 					StatusCode::INTERNAL_SERVER_ERROR,
 					"Failed to query database",
@@ -60,26 +73,30 @@ pub async fn ranking(query: Query<RankingParams>) -> impl IntoResponse {
 			}
 		},
 
-		_ => match pg_client().await {
+		_ => match pg_client(&state.pool).await {
 			Ok(pg_client) => {
 				let rank = rank.parse::<i32>().unwrap_or_else(|err| {
 					handle_error(&err.into());
 					1
 				});
 
-				pg_client
-					.query(
-						"
+				state
+					.metrics
+					.time_query(
+						"repository_ranking",
+						pg_client.query(
+							"
                             SELECT * FROM repository
                             WHERE visible = true AND quality = $1
                             ORDER BY quality ASC
                         ",
-						&[&rank],
+							&[&rank],
+						),
 					)
 					.await
 			}
 			Err(e) => {
-				eprintln!("[db] Failed to query database: {}", e);
+				error!(target: "db", "Failed to query database: {}", e);
 				return responses::error(
 					StatusCode::INTERNAL_SERVER_ERROR,
 					"Failed to query database",
@@ -88,10 +105,21 @@ pub async fn ranking(query: Query<RankingParams>) -> impl IntoResponse {
 		},
 	};
 
-	let repositories = match lookup {
+	let rows = match lookup {
 		Ok(rows) => rows,
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let repositories = match Repository::from_rows(&rows) {
+		Ok(repositories) => repositories,
+		Err(e) => {
+			error!(target: "db", "Failed to decode repository: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -99,24 +127,22 @@ pub async fn ranking(query: Query<RankingParams>) -> impl IntoResponse {
 		}
 	};
 
-	responses::data_with_count(
-		StatusCode::OK,
-		repositories
-			.iter()
-			.map(|row| {
-				let id: String = row.get("id");
+	let repositories = repositories
+		.into_iter()
+		.map(|repository| Repository {
+			refs: Some(RepositoryRefs {
+				meta: Some(format!(
+					"{}/jailbreak/repository/{}",
+					state.config.api_endpoint, repository.id
+				)),
+				packages: format!(
+					"{}/jailbreak/repository/{}/packages",
+					state.config.api_endpoint, repository.id
+				),
+			}),
+			..repository
+		})
+		.collect::<Vec<Repository>>();
 
-				return merge_json(
-					row_to_value(row),
-					json!({
-						"refs": {
-							"meta": format!("{}/jailbreak/repository/{}", api_endpoint(), id),
-							"packages": format!("{}/jailbreak/repository/{}/packages", api_endpoint(), id),
-						}
-					}),
-				);
-			})
-			.collect::<Vec<Value>>(),
-		repositories.len(),
-	)
+	responses::data_with_count(StatusCode::OK, &repositories, repositories.len())
 }
@@ -1,11 +1,13 @@
-use crate::{
-	helpers::responses,
-	utility::{handle_error, load_runtime_config},
+use crate::{helpers::responses, utility::handle_error, AppState};
+use axum::{
+	extract::{Query, State},
+	http::StatusCode,
+	response::IntoResponse,
 };
-use axum::{extract::Query, http::StatusCode, response::IntoResponse};
-use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, json};
+use std::time::Instant;
+use tracing::{debug, error};
 
 #[derive(Deserialize)]
 pub struct SafetyParams {
@@ -18,11 +20,11 @@ struct Repositories {
 	repositories: Vec<String>,
 }
 
-// TODO: Move into method body and implement new system like in goblar
-static REPOSITORIES: OnceCell<Vec<String>> = OnceCell::new();
-
-pub async fn safety(query: Query<SafetyParams>) -> impl IntoResponse {
-	if !set_repositories().await {
+pub async fn safety(
+	State(state): State<AppState>,
+	query: Query<SafetyParams>,
+) -> impl IntoResponse {
+	if !set_repositories(&state).await {
 		return responses::error(
 			StatusCode::INTERNAL_SERVER_ERROR,
 			"Unable to fetch repository list",
@@ -45,11 +47,14 @@ pub async fn safety(query: Query<SafetyParams>) -> impl IntoResponse {
 	};
 
 	let mut repositories = Vec::new();
-	let unsafe_repositories = match REPOSITORIES.get() {
+	let unsafe_repositories = match state.caches.piracy_repositories.get() {
 		Some(repositories) => repositories,
 		None => {
 			// TODO: Report Error
-			println!("Failed to get repository list (REPOSITORIES.get() returned None)");
+			error!(
+				target: "safety",
+				"Failed to get repository list (piracy_repositories.get() returned None)"
+			);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to get repository list",
@@ -75,10 +80,10 @@ pub async fn safety(query: Query<SafetyParams>) -> impl IntoResponse {
 	responses::data_with_count(StatusCode::OK, &repositories, repositories.len())
 }
 
-async fn set_repositories() -> bool {
-	if REPOSITORIES.get().is_none() {
-		let config = load_runtime_config();
-		let response = match reqwest::get(config.piracy_url).await {
+// TODO: Implement new system like in goblar
+async fn set_repositories(state: &AppState) -> bool {
+	if state.caches.piracy_repositories.get().is_none() {
+		let response = match state.http.get(&state.config.piracy_url).send().await {
 			Ok(response) => response,
 			Err(e) => panic!("Failed to fetch piracy URLs ({e})"),
 		};
@@ -99,44 +104,13 @@ async fn set_repositories() -> bool {
 			}
 		};
 
-		match REPOSITORIES.set(repositories) {
-			Ok(_) => {}
-			Err(_) => println!("Repository list already set"),
+		match state.caches.piracy_repositories.set(repositories) {
+			Ok(_) => {
+				let _ = state.caches.piracy_fetched_at.set(Instant::now());
+			}
+			Err(_) => debug!(target: "safety", "Repository list already set"),
 		};
 	}
 
 	true
 }
-
-pub async fn safety_healthy() -> bool {
-	let result = set_repositories().await;
-	if result == false {
-		return false;
-	}
-
-	let test_safe = "https://repo.chariz.com";
-	let test_unsafe = "https://repo.hackyouriphone.org";
-
-	let repositories = match REPOSITORIES.get() {
-		Some(repositories) => repositories,
-		None => {
-			println!("Failed to get repository list (REPOSITORIES.get() returned None)");
-			return false;
-		}
-	};
-
-	let mut safe_pass = true;
-	let mut unsafe_pass = false;
-
-	for unsafe_repository in repositories.iter() {
-		if test_safe.contains(unsafe_repository) {
-			safe_pass = false;
-		}
-
-		if test_unsafe.contains(unsafe_repository) {
-			unsafe_pass = true;
-		}
-	}
-
-	safe_pass && unsafe_pass
-}
@@ -1,19 +1,35 @@
 use crate::{
-	helpers::{pg_client, responses, row_to_value},
-	utility::{api_endpoint, merge_json, page_links},
+	helpers::{
+		pg_client,
+		responses::{self, PageRefs},
+		search::{keyset_sql, page_cursors, search_sql, Cursor, Direction, SearchMode},
+	},
+	types::{FromRow, Repository, RepositoryRefs},
+	utility::cursor_links,
+	AppState,
 };
-use axum::{extract::Query, http::StatusCode, response::IntoResponse};
+use axum::{
+	extract::{Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+};
+use deadpool_postgres::tokio_postgres::types::{ToSql, Type};
 use serde::Deserialize;
-use serde_json::{json, Value};
+use tracing::error;
 
 #[derive(Deserialize)]
 pub struct SearchParams {
 	q: Option<String>,
 	limit: Option<u8>,
 	page: Option<u8>,
+	cursor: Option<String>,
+	mode: Option<String>,
 }
 
-pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
+pub async fn search(
+	State(state): State<AppState>,
+	query: Query<SearchParams>,
+) -> impl IntoResponse {
 	let q = match &query.q {
 		Some(q) => {
 			if q.len() < 2 {
@@ -61,47 +77,157 @@ pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
 		None => 100,
 	};
 
-	let repositories = match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				// Need to support our legacy fields
-				.query(
-					"
-							SELECT
-								*,
-								repository.id AS slug,
-								repository.quality AS tier,
-								repository.bootstrap AS isBootstrap,
-								ts_rank(search_vector, plainto_tsquery('simple', $1)) AS rank
-							FROM repository
-							WHERE
-								visible = true
-								AND search_vector @@ plainto_tsquery('simple', $1)
-							ORDER BY
-								rank DESC,
-								quality ASC
-							LIMIT $2 OFFSET $3
-	                ",
-					&[
-						&q.to_string(),
-						&(limit as i64),
-						&(((page - 1) * limit) as i64),
-					],
-				)
-				.await
-			{
-				Ok(rows) => rows,
-				Err(e) => {
-					eprintln!("[db] Failed to query database: {}", e);
-					return responses::error(
-						StatusCode::INTERNAL_SERVER_ERROR,
-						"Failed to query database",
-					);
-				}
+	let mode = match &query.mode {
+		Some(mode) => match mode.parse::<SearchMode>() {
+			Ok(mode) => mode,
+			Err(_) => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'mode\' must be exact, prefix or fuzzy",
+				);
 			}
+		},
+
+		None => SearchMode::default(),
+	};
+
+	let cursor = match &query.cursor {
+		Some(token) => match Cursor::decode(token, &state.caches.signing_key) {
+			Some(cursor) => Some(cursor),
+			None => {
+				return responses::error(
+					StatusCode::BAD_REQUEST,
+					"Query parameter \'cursor\' is invalid",
+				);
+			}
+		},
+
+		None => None,
+	};
+
+	let sql = search_sql(
+		mode,
+		q,
+		"repository",
+		"coalesce(repository.name, repository.id)",
+		2,
+	);
+
+	// The count query shares every parameter except the pagination ones
+	let mut params: Vec<&(dyn ToSql + Sync)> = vec![q];
+	if let Some(tsquery) = &sql.tsquery {
+		params.push(tsquery);
+	}
+
+	let mut page_params = params.clone();
+	let direction = match &cursor {
+		Some(cursor) => cursor.direction,
+		None => Direction::Next,
+	};
+
+	let keyset = keyset_sql(
+		direction,
+		&sql.rank,
+		"repository.quality",
+		"repository.id",
+		params.len() + 1,
+	);
+
+	let mut condition = sql.condition.clone();
+	if let Some(cursor) = &cursor {
+		page_params.push(&cursor.rank);
+		page_params.push(&cursor.quality);
+		page_params.push(&cursor.id);
+		condition = format!("{} AND {}", condition, keyset.condition);
+	}
+
+	// One extra row is read to tell whether another page follows
+	let limit_param = i64::from(limit) + 1;
+	let offset = match &cursor {
+		Some(_) => 0,
+		None => (i64::from(page) - 1) * i64::from(limit),
+	};
+
+	page_params.push(&limit_param);
+	page_params.push(&offset);
+
+	// Need to support our legacy fields
+	let page_query = format!(
+		"
+		SELECT
+			*,
+			repository.id AS slug,
+			repository.quality AS tier,
+			repository.bootstrap AS isBootstrap,
+			{} AS rank
+		FROM repository
+		WHERE
+			visible = true
+			AND {}
+		ORDER BY {}
+		LIMIT ${} OFFSET ${}
+	",
+		sql.rank,
+		condition,
+		keyset.order,
+		page_params.len() - 1,
+		page_params.len()
+	);
+
+	// Counted separately so that pages past the end still report the total
+	let total_query = format!(
+		"
+		SELECT count(*) AS total FROM repository
+		WHERE
+			visible = true
+			AND {}
+	",
+		sql.condition
+	);
+
+	let pg_client = match pg_client(&state.pool).await {
+		Ok(pg_client) => pg_client,
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
+		}
+	};
+
+	let (mut rows, total) = match state
+		.metrics
+		.time_query("repository_search", async {
+			tokio::try_join!(pg_client.query(&page_query, &page_params), async {
+				// Prefix mode never references the raw query when counting
+				// so its type has to be given up front
+				let statement = pg_client.prepare_typed(&total_query, &[Type::TEXT]).await?;
+				pg_client.query_one(&statement, &params).await
+			})
+		})
+		.await
+	{
+		Ok((rows, total)) => (rows, total.get::<_, i64>("total")),
+		Err(e) => {
+			error!(target: "db", "Failed to query database: {}", e);
+			return responses::error(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Failed to query database",
+			);
 		}
+	};
+
+	let has_more = rows.len() > usize::from(limit);
+	rows.truncate(usize::from(limit));
+	if direction == Direction::Previous {
+		rows.reverse();
+	}
+
+	let repositories = match Repository::from_rows(&rows) {
+		Ok(repositories) => repositories,
 		Err(e) => {
-			eprintln!("[db] Failed to query database: {}", e);
+			error!(target: "db", "Failed to decode repository: {}", e);
 			return responses::error(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				"Failed to query database",
@@ -109,61 +235,51 @@ pub async fn search(query: Query<SearchParams>) -> impl IntoResponse {
 		}
 	};
 
-	let next = repositories.len() == limit as usize;
-	let (prev_page, next_page) = page_links("/jailbreak/repository/search", page, next);
+	let repositories = repositories
+		.into_iter()
+		.map(|repository| Repository {
+			refs: Some(RepositoryRefs {
+				meta: Some(format!(
+					"{}/jailbreak/repository/{}",
+					state.config.api_endpoint, repository.id
+				)),
+				packages: format!(
+					"{}/jailbreak/repository/{}/packages",
+					state.config.api_endpoint, repository.id
+				),
+			}),
+			..repository
+		})
+		.collect::<Vec<Repository>>();
+
+	let (prev_cursor, next_cursor) = page_cursors(
+		&state.caches.signing_key,
+		&rows,
+		cursor.as_ref(),
+		page,
+		has_more,
+	);
+	let (prev_page, next_page) = cursor_links(
+		&state.config.api_endpoint,
+		"/jailbreak/repository/search",
+		&[
+			("q", query.q.clone()),
+			("limit", query.limit.map(|limit| limit.to_string())),
+			("mode", query.mode.clone()),
+		],
+		prev_cursor,
+		next_cursor,
+	);
 
-	responses::data_with_count_and_refs(
+	responses::data_with_total_and_refs(
 		StatusCode::OK,
-		&repositories
-			.iter()
-			.map(|row| {
-				let id: String = row.get("id");
-				merge_json(
-					row_to_value(row),
-					json!({
-						"refs": {
-							"meta": format!("{}/jailbreak/repository/{}", api_endpoint(), id),
-							"packages": format!("{}/jailbreak/repository/{}/packages", api_endpoint(), id)
-						}
-					}),
-				)
-			})
-			.collect::<Vec<Value>>(),
+		&repositories,
 		repositories.len(),
-		json!({
-			"nextPage": next_page,
-			"previousPage": prev_page,
-		}),
+		total,
+		next_page.is_some(),
+		PageRefs {
+			nextPage: next_page,
+			previousPage: prev_page,
+		},
 	)
 }
-
-pub async fn search_healthy() -> bool {
-	match pg_client().await {
-		Ok(pg_client) => {
-			match pg_client
-				.query(
-					"
-						SELECT *, ts_rank(
-							search_vector,
-							plainto_tsquery('simple', 'havoc')
-                        ) AS rank
-                        FROM repository
-                        WHERE
-                            visible = true
-                            AND search_vector @@ plainto_tsquery('simple', 'havoc')
-                        ORDER BY
-                        	rank DESC,
-                         	quality ASC
-                        LIMIT 1 OFFSET 0
-                    ",
-					&[],
-				)
-				.await
-			{
-				Ok(_) => true,
-				Err(_) => false,
-			}
-		}
-		Err(_) => false,
-	}
-}
@@ -0,0 +1,410 @@
+use crate::utility::RuntimeConfig;
+use anyhow::{anyhow, Context, Error, Result};
+use axum::{extract::connect_info::Connected, Router};
+use hyper::server::{accept::Accept, conn::AddrStream};
+use rustls_pemfile::{read_one, Item};
+use serde::{Deserialize, Serialize};
+use std::{
+	fmt,
+	fs::{remove_file, symlink_metadata, File},
+	future::Future,
+	io::{self, BufReader, ErrorKind},
+	net::{IpAddr, SocketAddr},
+	os::unix::fs::FileTypeExt,
+	path::{Path, PathBuf},
+	pin::Pin,
+	str::FromStr,
+	sync::Arc,
+	task::{Context as TaskContext, Poll},
+	time::Duration,
+};
+use tokio::{
+	net::{TcpListener, TcpStream, UnixListener, UnixStream},
+	signal::{
+		ctrl_c,
+		unix::{signal, SignalKind},
+	},
+	sync::{mpsc, watch},
+	task::JoinSet,
+	time::timeout,
+};
+use tokio_rustls::{
+	rustls::{Certificate, PrivateKey, ServerConfig},
+	server::TlsStream,
+	TlsAcceptor,
+};
+use tracing::{error, info, warn};
+
+/// Time a client gets to finish the TLS handshake before it is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An address the HTTP server accepts connections on
+/// Written as `host:port` (`[::]:3000` for IPv6) or `unix:/path/to/socket`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Listener {
+	Tcp(SocketAddr),
+	Unix(PathBuf),
+}
+
+impl FromStr for Listener {
+	type Err = Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value.strip_prefix("unix:") {
+			Some("") => Err(anyhow!("Unix socket listeners need a path")),
+			Some(path) => Ok(Listener::Unix(PathBuf::from(path))),
+			None => match value.parse::<SocketAddr>() {
+				Ok(addr) => Ok(Listener::Tcp(addr)),
+				Err(e) => Err(anyhow!("Invalid listen address '{}' ({})", value, e)),
+			},
+		}
+	}
+}
+
+impl TryFrom<String> for Listener {
+	type Error = Error;
+
+	fn try_from(value: String) -> Result<Self> {
+		value.parse()
+	}
+}
+
+impl From<Listener> for String {
+	fn from(listener: Listener) -> Self {
+		listener.to_string()
+	}
+}
+
+impl fmt::Display for Listener {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Listener::Tcp(addr) => write!(f, "{}", addr),
+			Listener::Unix(path) => write!(f, "unix:{}", path.display()),
+		}
+	}
+}
+
+/// Address of the peer a request came from, available through `ConnectInfo`
+/// Connections over a Unix socket have no address to report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientAddr(pub Option<IpAddr>);
+
+impl Connected<&AddrStream> for ClientAddr {
+	fn connect_info(stream: &AddrStream) -> Self {
+		ClientAddr(Some(stream.remote_addr().ip()))
+	}
+}
+
+impl Connected<&TlsStream<TcpStream>> for ClientAddr {
+	fn connect_info(stream: &TlsStream<TcpStream>) -> Self {
+		ClientAddr(stream.get_ref().0.peer_addr().ok().map(|addr| addr.ip()))
+	}
+}
+
+impl Connected<&UnixStream> for ClientAddr {
+	fn connect_info(_: &UnixStream) -> Self {
+		ClientAddr(None)
+	}
+}
+
+/// Accepts connections on a Unix socket
+struct UnixIncoming(UnixListener);
+
+impl Accept for UnixIncoming {
+	type Conn = UnixStream;
+	type Error = io::Error;
+
+	fn poll_accept(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+	) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+		self.0
+			.poll_accept(cx)
+			.map(|result| Some(result.map(|(stream, _)| stream)))
+	}
+}
+
+/// Accepts TLS connections once their handshake completes
+/// Handshakes run in their own tasks so a slow client can't stall the others
+struct TlsIncoming(mpsc::Receiver<TlsStream<TcpStream>>);
+
+impl TlsIncoming {
+	fn new(listener: TcpListener, acceptor: TlsAcceptor) -> Self {
+		let (sender, receiver) = mpsc::channel(64);
+
+		tokio::spawn(async move {
+			loop {
+				// The server dropping its end means it has shut down
+				let accepted = tokio::select! {
+					_ = sender.closed() => break,
+					accepted = listener.accept() => accepted,
+				};
+
+				let stream = match accepted {
+					Ok((stream, _)) => stream,
+					Err(e) => {
+						warn!(target: "http", "Failed to accept a connection: {}", e);
+						continue;
+					}
+				};
+
+				let acceptor = acceptor.clone();
+				let sender = sender.clone();
+				tokio::spawn(async move {
+					match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+						Ok(Ok(stream)) => {
+							let _ = sender.send(stream).await;
+						}
+						Ok(Err(e)) => warn!(target: "http", "TLS handshake failed: {}", e),
+						Err(_) => warn!(target: "http", "TLS handshake timed out"),
+					}
+				});
+			}
+		});
+
+		TlsIncoming(receiver)
+	}
+}
+
+impl Accept for TlsIncoming {
+	type Conn = TlsStream<TcpStream>;
+	type Error = io::Error;
+
+	fn poll_accept(
+		mut self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+	) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+		self.0.poll_recv(cx).map(|stream| stream.map(Ok))
+	}
+}
+
+/// Reads the certificate chain and private key used to terminate TLS
+fn tls_acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor> {
+	let mut reader = BufReader::new(
+		File::open(cert)
+			.with_context(|| format!("Failed to open TLS certificate {}", cert.display()))?,
+	);
+
+	let certs = rustls_pemfile::certs(&mut reader)
+		.with_context(|| format!("Failed to read TLS certificate {}", cert.display()))?
+		.into_iter()
+		.map(Certificate)
+		.collect::<Vec<Certificate>>();
+
+	if certs.is_empty() {
+		return Err(anyhow!("No certificates found in {}", cert.display()));
+	}
+
+	let mut reader = BufReader::new(
+		File::open(key).with_context(|| format!("Failed to open TLS key {}", key.display()))?,
+	);
+
+	let key = loop {
+		match read_one(&mut reader)? {
+			Some(Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key)) => {
+				break PrivateKey(key)
+			}
+			Some(_) => continue,
+			None => return Err(anyhow!("No private key found in {}", key.display())),
+		}
+	};
+
+	let mut config = ServerConfig::builder()
+		.with_safe_defaults()
+		.with_no_client_auth()
+		.with_single_cert(certs, key)?;
+
+	config.alpn_protocols = vec![b"http/1.1".to_vec()];
+	Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Removes a socket left behind by a previous process, which would fail the bind
+/// Anything else at the path is refused rather than deleted
+fn remove_stale_socket(path: &Path) -> Result<()> {
+	let metadata = match symlink_metadata(path) {
+		Ok(metadata) => metadata,
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(e.into()),
+	};
+
+	if !metadata.file_type().is_socket() {
+		return Err(anyhow!("{} exists and is not a socket", path.display()));
+	}
+
+	remove_file(path)?;
+	Ok(())
+}
+
+/// Resolves once the process is asked to stop with SIGTERM or SIGINT
+pub async fn shutdown_signal() {
+	let terminate = async {
+		match signal(SignalKind::terminate()) {
+			Ok(mut terminate) => {
+				terminate.recv().await;
+			}
+			Err(e) => {
+				error!(target: "http", "Failed to listen for SIGTERM: {}", e);
+				std::future::pending::<()>().await;
+			}
+		}
+	};
+
+	tokio::select! {
+		_ = ctrl_c() => {},
+		_ = terminate => {},
+	}
+}
+
+/// Serves the router on every configured listener until `shutdown` resolves
+/// In-flight requests are then given `shutdown_timeout` seconds to finish
+/// before the remaining connections are dropped
+pub async fn serve<F>(app: Router, config: &RuntimeConfig, shutdown: F) -> Result<()>
+where
+	F: Future<Output = ()>,
+{
+	if config.listen.is_empty() {
+		return Err(anyhow!("No listen addresses are configured"));
+	}
+
+	let tls = match (config.tls_cert.is_empty(), config.tls_key.is_empty()) {
+		(true, true) => None,
+		(false, false) => Some(tls_acceptor(
+			Path::new(&config.tls_cert),
+			Path::new(&config.tls_key),
+		)?),
+		_ => return Err(anyhow!("tls_cert and tls_key must be set together")),
+	};
+
+	let (stop, stopped) = watch::channel(false);
+	let mut servers = JoinSet::new();
+
+	for listener in &config.listen {
+		let make_service = app
+			.clone()
+			.into_make_service_with_connect_info::<ClientAddr>();
+		let mut stopped = stopped.clone();
+		let signal = async move {
+			let _ = stopped.wait_for(|stopped| *stopped).await;
+		};
+
+		match (listener, &tls) {
+			(Listener::Tcp(addr), None) => {
+				let server = axum::Server::try_bind(addr)
+					.with_context(|| format!("Failed to bind {}", listener))?
+					.serve(make_service)
+					.with_graceful_shutdown(signal);
+
+				servers.spawn(async move { server.await.map_err(Error::from) });
+				info!(target: "http", "Listening on {}", listener);
+			}
+
+			(Listener::Tcp(addr), Some(acceptor)) => {
+				let incoming = TcpListener::bind(addr)
+					.await
+					.with_context(|| format!("Failed to bind {}", listener))?;
+
+				let server = axum::Server::builder(TlsIncoming::new(incoming, acceptor.clone()))
+					.serve(make_service)
+					.with_graceful_shutdown(signal);
+
+				servers.spawn(async move { server.await.map_err(Error::from) });
+				info!(target: "http", "Listening on {} (tls)", listener);
+			}
+
+			(Listener::Unix(path), _) => {
+				remove_stale_socket(path)
+					.with_context(|| format!("Failed to remove stale socket {}", listener))?;
+
+				let incoming = UnixListener::bind(path)
+					.with_context(|| format!("Failed to bind {}", listener))?;
+
+				let server = axum::Server::builder(UnixIncoming(incoming))
+					.serve(make_service)
+					.with_graceful_shutdown(signal);
+
+				servers.spawn(async move { server.await.map_err(Error::from) });
+				info!(target: "http", "Listening on {}", listener);
+			}
+		}
+	}
+
+	tokio::select! {
+		_ = shutdown => {},
+		Some(result) = servers.join_next() => {
+			servers.abort_all();
+			return match result {
+				Ok(Ok(())) => Err(anyhow!("A listener stopped unexpectedly")),
+				Ok(Err(e)) => Err(e),
+				Err(e) => Err(e.into()),
+			};
+		}
+	}
+
+	info!(
+		target: "http",
+		"Shutting down, waiting up to {}s for requests to finish",
+		config.shutdown_timeout
+	);
+
+	let _ = stop.send(true);
+	let drained = timeout(Duration::from_secs(config.shutdown_timeout), async {
+		while let Some(result) = servers.join_next().await {
+			match result {
+				Ok(Ok(())) => {}
+				Ok(Err(e)) => error!(target: "http", "Listener failed while shutting down: {}", e),
+				Err(e) => error!(target: "http", "Listener panicked while shutting down: {}", e),
+			}
+		}
+	})
+	.await;
+
+	if drained.is_err() {
+		warn!(target: "http", "Requests were still running after the timeout, dropping them");
+		servers.abort_all();
+	}
+
+	for listener in &config.listen {
+		if let Listener::Unix(path) = listener {
+			let _ = remove_file(path);
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn listeners_parse_and_display() {
+		for value in ["0.0.0.0:3000", "[::]:3000", "unix:/run/canister.sock"] {
+			assert_eq!(value.parse::<Listener>().unwrap().to_string(), value);
+		}
+
+		assert_eq!(
+			"unix:/run/canister.sock".parse::<Listener>().unwrap(),
+			Listener::Unix(PathBuf::from("/run/canister.sock"))
+		);
+
+		assert!("localhost".parse::<Listener>().is_err());
+		assert!("unix:".parse::<Listener>().is_err());
+	}
+
+	#[test]
+	fn only_stale_sockets_are_removed() {
+		let dir = tempfile::tempdir().unwrap();
+
+		let socket = dir.path().join("canister.sock");
+		drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+		remove_stale_socket(&socket).unwrap();
+		assert!(!socket.exists());
+		remove_stale_socket(&socket).unwrap();
+
+		let file = dir.path().join("canister.toml");
+		File::create(&file).unwrap();
+		assert!(remove_stale_socket(&file).is_err());
+		assert!(file.exists());
+	}
+}
@@ -0,0 +1,109 @@
+use crate::{
+	helpers::{
+		apt::AptIndices,
+		create_pool,
+		health::Readiness,
+		ingest::{IngestLimits, IngestOptions, IngestQueue, Sink},
+		search::{SearchEngine, Typesense},
+	},
+	metrics::Metrics,
+	utility::{signing_key, RuntimeConfig},
+};
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use once_cell::sync::OnceCell;
+use reqwest::Client;
+use std::{
+	collections::HashMap,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Everything the route handlers share, handed to them through `State`
+/// Cloning is cheap since every field is reference counted
+#[derive(Clone)]
+pub struct AppState {
+	pub config: Arc<RuntimeConfig>,
+	pub pool: Pool,
+	/// Client for outbound requests, like forwarding download events
+	pub http: Client,
+	/// Engine used for package search when a request doesn't pick one
+	pub search_engine: SearchEngine,
+	pub typesense: Arc<Typesense>,
+	pub caches: Arc<Caches>,
+	pub metrics: Arc<Metrics>,
+	/// Queue that download events are delivered to the sinks through
+	pub ingest: Arc<IngestQueue>,
+	/// Rate limits and deduplication for download events
+	pub ingest_limits: Arc<IngestLimits>,
+}
+
+/// Values that are computed once and reused by every request
+pub struct Caches {
+	/// Key that pagination cursors are signed with
+	pub signing_key: Vec<u8>,
+	/// Repositories known for piracy, fetched on first use
+	pub piracy_repositories: OnceCell<Vec<String>>,
+	/// When the piracy list was fetched, exported as its age
+	pub piracy_fetched_at: OnceCell<Instant>,
+	/// Last readiness report and when it was checked
+	pub readiness: Mutex<Option<(Instant, Readiness)>>,
+	/// APT indices by repository ID and when they were built
+	pub apt_indices: Mutex<HashMap<String, (Instant, Arc<AptIndices>)>>,
+}
+
+impl AppState {
+	/// Connects to the database in the config and builds the state around it
+	pub async fn new(config: RuntimeConfig) -> Result<Self> {
+		let pool = create_pool(&config.database_url, config.pool_size).await?;
+		Self::with_pool(config, pool)
+	}
+
+	/// Builds the state around an existing pool, so embedders can share theirs
+	/// Needs a tokio runtime, since it starts delivering spooled download events
+	pub fn with_pool(config: RuntimeConfig, pool: Pool) -> Result<Self> {
+		let search_engine = config.search_engine.parse::<SearchEngine>()?;
+		let typesense = Typesense::new(&config.typesense_url, &config.typesense_api_key)?;
+		let http = Client::builder().timeout(Duration::from_secs(5)).build()?;
+
+		info!(
+			target: "search",
+			"Using {} as the default search engine",
+			search_engine
+		);
+
+		let caches = Caches {
+			signing_key: signing_key(&config.cursor_secret),
+			piracy_repositories: OnceCell::new(),
+			piracy_fetched_at: OnceCell::new(),
+			readiness: Mutex::new(None),
+			apt_indices: Mutex::new(HashMap::new()),
+		};
+
+		let metrics = Arc::new(Metrics::new()?);
+		let sinks = config
+			.ingest_sinks
+			.iter()
+			.map(|sink| Sink::new(sink, &http, &config))
+			.collect::<Result<Vec<Sink>>>()?;
+
+		let ingest =
+			IngestQueue::start(IngestOptions::from_config(&config), sinks, metrics.clone())?;
+
+		let ingest_limits = IngestLimits::from_config(&config);
+
+		Ok(AppState {
+			config: Arc::new(config),
+			pool,
+			http,
+			search_engine,
+			typesense: Arc::new(typesense),
+			caches: Arc::new(caches),
+			metrics,
+			ingest: Arc::new(ingest),
+			ingest_limits: Arc::new(ingest_limits),
+		})
+	}
+}
@@ -1,5 +1,17 @@
+use anyhow::Result;
+use deadpool_postgres::tokio_postgres::Row;
+
 mod package;
 mod repository;
 
-// pub use self::package::*;
+pub use self::package::*;
 pub use self::repository::*;
+
+/// A response model that is decoded from a database row
+pub trait FromRow: Sized {
+	fn from_row(row: &Row) -> Result<Self>;
+
+	fn from_rows(rows: &[Row]) -> Result<Vec<Self>> {
+		rows.iter().map(Self::from_row).collect()
+	}
+}
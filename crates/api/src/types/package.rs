@@ -1,38 +1,89 @@
-#![allow(non_snake_case)]
-use super::Repository;
-use serde::{Deserialize, Serialize};
+use super::FromRow;
+use crate::utility::compatible_architectures;
+use anyhow::Result;
+use deadpool_postgres::tokio_postgres::Row;
+pub use models::{
+	Dependent, DependentRelations, HashChange, InstallPlan, Package, PackageRefs, PackageUpdate,
+	PackageVersion, PlannedPackage, RepositoryVersions, ResolveFailure, SearchPackage, SizeChange,
+	Unsatisfiable, UpdateChanges, UpdateRefs,
+};
+use serde_json::{from_value, Value};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Package {
-	pub id: String,
-	pub package_id: String,
-	pub latest_version: bool,
-	pub visible: bool,
-	pub quality: i32,
-	pub repository_id: String,
-	pub price: String,
-	pub version: String,
-	pub architecture: String,
-	pub package_filename: String,
-	pub package_size: i64,
-	pub sha256_hash: Option<String>,
-	pub name: Option<String>,
-	pub description: Option<String>,
-	pub author: Option<String>,
-	pub maintainer: Option<String>,
-	pub depiction: Option<String>,
-	pub native_depiction: Option<String>,
-	pub sileo_depiction: Option<String>,
-	pub header_url: Option<String>,
-	pub tint_color: Option<String>,
-	pub icon_url: Option<String>,
-	pub section: Option<String>,
-	pub tags: Option<Vec<String>>,
-	pub installed_size: Option<i64>,
+impl FromRow for Package {
+	fn from_row(row: &Row) -> Result<Self> {
+		Ok(Package {
+			id: row.try_get("id")?,
+			package_id: row.try_get("package_id")?,
+			latest_version: row.try_get("latest_version")?,
+			visible: row.try_get("visible")?,
+			quality: row.try_get("quality")?,
+			repository_id: row.try_get("repository_id")?,
+			price: row.try_get("price")?,
+			version: row.try_get("version")?,
+			architecture: row.try_get("architecture")?,
+			package_filename: row.try_get("package_filename")?,
+			package_size: row.try_get("package_size")?,
+			sha256_hash: row.try_get("sha256_hash")?,
+			name: row.try_get("name")?,
+			description: row.try_get("description")?,
+			author: row.try_get("author")?,
+			maintainer: row.try_get("maintainer")?,
+			depiction: row.try_get("depiction")?,
+			native_depiction: row.try_get("native_depiction")?,
+			sileo_depiction: row.try_get("sileo_depiction")?,
+			header_url: row.try_get("header_url")?,
+			tint_color: row.try_get("tint_color")?,
+			icon_url: row.try_get("icon_url")?,
+			section: row.try_get("section")?,
+			tags: row.try_get("tags")?,
+			installed_size: row.try_get("installed_size")?,
+			depends: row.try_get("depends")?,
+			pre_depends: row.try_get("pre_depends")?,
+			conflicts: row.try_get("conflicts")?,
+			breaks: row.try_get("breaks")?,
+			provides: row.try_get("provides")?,
+			replaces: row.try_get("replaces")?,
 
-	// Old fields that are grandfathered in
-	pub package: String,
-	pub repositoryTier: i32,
-	pub sileoDepiction: Option<String>,
-	pub repository: Repository,
+			compatible_architectures: None,
+			relations: None,
+			refs: None,
+		})
+	}
+}
+
+/// Search rows embed their repository as JSON in the `repository` column
+impl FromRow for SearchPackage {
+	#[allow(deprecated)]
+	fn from_row(row: &Row) -> Result<Self> {
+		let base = Package::from_row(row)?;
+		let repository = from_value(row.try_get::<_, Value>("repository")?)?;
+
+		Ok(SearchPackage {
+			package: base.package_id.clone(),
+			repositoryTier: base.quality,
+			sileoDepiction: base.sileo_depiction.clone(),
+			repository,
+			base,
+		})
+	}
+}
+
+impl FromRow for PackageVersion {
+	fn from_row(row: &Row) -> Result<Self> {
+		let architecture: String = row.try_get("architecture")?;
+
+		Ok(PackageVersion {
+			id: row.try_get("id")?,
+			version: row.try_get("version")?,
+			compatible_architectures: compatible_architectures(&architecture)
+				.into_iter()
+				.map(str::to_string)
+				.collect(),
+			architecture,
+			latest_version: row.try_get("latest_version")?,
+			package_filename: row.try_get("package_filename")?,
+			package_size: row.try_get("package_size")?,
+			sha256_hash: row.try_get("sha256_hash")?,
+		})
+	}
 }
@@ -1,39 +1,50 @@
-#![allow(non_snake_case)]
-use serde::{Deserialize, Serialize};
+use super::FromRow;
+use anyhow::Result;
+use deadpool_postgres::tokio_postgres::Row;
+pub use models::{Repository, RepositoryRefs};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Repository {
-	pub id: String,
-	pub aliases: Option<Vec<String>>,
-	pub visible: bool,
-	pub quality: i32,
-	pub package_count: i64,
-	pub sections: Vec<String>,
-	pub bootstrap: bool,
-	pub uri: String,
-	pub suite: String,
-	pub component: Option<String>,
-	pub name: Option<String>,
-	pub version: Option<String>,
-	pub description: Option<String>,
-	pub date: Option<String>,
-	pub payment_gateway: Option<String>,
-	pub sileo_endpoint: Option<String>,
+impl FromRow for Repository {
+	#[allow(deprecated)]
+	fn from_row(row: &Row) -> Result<Self> {
+		let id: String = row.try_get("id")?;
+		let quality: i32 = row.try_get("quality")?;
+		let bootstrap: bool = row.try_get("bootstrap")?;
 
-	pub origin_hostname: String,
-	pub origin_release_path: String,
-	pub origin_release_hash: String,
-	pub origin_packages_path: String,
-	pub origin_packages_hash: String,
-	pub origin_last_updated: String,
-	pub origin_has_in_release: bool,
-	pub origin_has_release_gpg: bool,
-	pub origin_supports_payment_v1: bool,
-	pub origin_supports_payment_v2: bool,
-	pub origin_uses_https: bool,
+		Ok(Repository {
+			slug: id.clone(),
+			tier: quality,
+			isBootstrap: bootstrap,
 
-	// Old fields that are grandfathered in
-	pub slug: String,
-	pub tier: i32,
-	pub isBootstrap: bool,
+			id,
+			aliases: row.try_get("aliases")?,
+			visible: row.try_get("visible")?,
+			quality,
+			package_count: row.try_get("package_count")?,
+			sections: row.try_get("sections")?,
+			bootstrap,
+			uri: row.try_get("uri")?,
+			suite: row.try_get("suite")?,
+			component: row.try_get("component")?,
+			name: row.try_get("name")?,
+			version: row.try_get("version")?,
+			description: row.try_get("description")?,
+			date: row.try_get("date")?,
+			payment_gateway: row.try_get("payment_gateway")?,
+			sileo_endpoint: row.try_get("sileo_endpoint")?,
+
+			origin_hostname: row.try_get("origin_hostname")?,
+			origin_release_path: row.try_get("origin_release_path")?,
+			origin_release_hash: row.try_get("origin_release_hash")?,
+			origin_packages_path: row.try_get("origin_packages_path")?,
+			origin_packages_hash: row.try_get("origin_packages_hash")?,
+			origin_last_updated: row.try_get("origin_last_updated")?,
+			origin_has_in_release: row.try_get("origin_has_in_release")?,
+			origin_has_release_gpg: row.try_get("origin_has_release_gpg")?,
+			origin_supports_payment_v1: row.try_get("origin_supports_payment_v1")?,
+			origin_supports_payment_v2: row.try_get("origin_supports_payment_v2")?,
+			origin_uses_https: row.try_get("origin_uses_https")?,
+
+			refs: None,
+		})
+	}
 }
@@ -1,11 +1,8 @@
-use super::{handle_error, load_runtime_config};
+use super::handle_error;
 use serde::Serialize;
 use serde_json::{to_value, Value};
-use std::sync::OnceLock;
 use url::Url;
 
-static API_ENDPOINT: OnceLock<String> = OnceLock::new();
-
 /// Merges two JSON objects together in the order of left, right
 /// If the object is a strictly-typed struct, it is serialized into a Value
 pub fn merge_json<L: Serialize, R: Serialize>(left: L, right: R) -> Value {
@@ -43,54 +40,99 @@ fn merge_json_value(left: &mut Value, right: Value) {
 	}
 }
 
-/// Generates pagination links with the given URL path and page number
-/// The next parameter determines if this is the last page or not
-pub fn page_links(path: &str, page: u8, next: bool) -> (Option<String>, Option<String>) {
-	let endpoint = API_ENDPOINT.get_or_init(|| {
-		let config = load_runtime_config();
-		config.api_endpoint
-	});
-
+/// Builds an API URL for the path, keeping the given query parameters
+/// Parameters without a value are left out of the URL
+fn endpoint_url(endpoint: &str, path: &str, query: &[(&str, Option<String>)]) -> Option<Url> {
 	let url = format!("{}{}", endpoint, path);
 	let mut url = match Url::parse(&url) {
 		Ok(url) => url,
 		Err(err) => {
 			handle_error(&err.into());
-			return (None, None);
+			return None;
 		}
 	};
 
+	for (key, value) in query {
+		if let Some(value) = value {
+			url.query_pairs_mut().append_pair(key, value);
+		}
+	}
+
+	Some(url)
+}
+
+/// Appends a single query parameter to a copy of the URL
+fn with_pair(url: &Url, key: &str, value: &str) -> String {
+	url.clone()
+		.query_pairs_mut()
+		.append_pair(key, value)
+		.finish()
+		.as_str()
+		.to_owned()
+}
+
+/// Generates pagination links that keep the given query parameters
+/// The next parameter determines if this is the last page or not
+pub fn page_links_with_query(
+	endpoint: &str,
+	path: &str,
+	query: &[(&str, Option<String>)],
+	page: u8,
+	next: bool,
+) -> (Option<String>, Option<String>) {
+	let url = match endpoint_url(endpoint, path, query) {
+		Some(url) => url,
+		None => return (None, None),
+	};
+
 	let prev_page = match page > 1 {
-		true => Some(
-			url.clone()
-				.query_pairs_mut()
-				.append_pair("page", (page - 1).to_string().as_str())
-				.finish()
-				.as_str()
-				.to_owned(),
-		),
+		true => Some(with_pair(&url, "page", &(page - 1).to_string())),
 		false => None,
 	};
 
-	let next_page = match next {
-		true => Some(
-			url.query_pairs_mut()
-				.append_pair("page", (page + 1).to_string().as_str())
-				.finish()
-				.as_str()
-				.to_owned(),
-		),
-		false => None,
+	// The last page a u8 can address has no link past it
+	let next_page = match (next, page.checked_add(1)) {
+		(true, Some(next_page)) => Some(with_pair(&url, "page", &next_page.to_string())),
+		_ => None,
 	};
 
 	(prev_page, next_page)
 }
 
-pub fn api_endpoint() -> String {
-	API_ENDPOINT
-		.get_or_init(|| {
-			let config = load_runtime_config();
-			config.api_endpoint
-		})
-		.clone()
+/// Generates pagination links which continue from opaque cursors
+/// A link is only generated when its cursor is given
+pub fn cursor_links(
+	endpoint: &str,
+	path: &str,
+	query: &[(&str, Option<String>)],
+	prev_cursor: Option<String>,
+	next_cursor: Option<String>,
+) -> (Option<String>, Option<String>) {
+	let url = match endpoint_url(endpoint, path, query) {
+		Some(url) => url,
+		None => return (None, None),
+	};
+
+	(
+		prev_cursor.map(|cursor| with_pair(&url, "cursor", &cursor)),
+		next_cursor.map(|cursor| with_pair(&url, "cursor", &cursor)),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn last_addressable_page_has_no_next_link() {
+		let query = [("limit", Some("10".to_string()))];
+		let (prev_page, next_page) =
+			page_links_with_query("https://api.canister.me/v2", "/packages", &query, 255, true);
+
+		assert_eq!(
+			prev_page.as_deref(),
+			Some("https://api.canister.me/v2/packages?limit=10&page=254")
+		);
+		assert_eq!(next_page, None);
+	}
 }
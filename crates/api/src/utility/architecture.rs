@@ -0,0 +1,109 @@
+use axum::http::HeaderMap;
+
+/// Architectures that a jailbroken client can report for itself
+/// `iphoneos-arm` is the rootful layout, `iphoneos-arm64` is rootless
+/// and `iphoneos-arm64e` is used by roothide
+pub const CLIENT_ARCHITECTURES: [&str; 7] = [
+	"iphoneos-arm",
+	"iphoneos-arm64",
+	"iphoneos-arm64e",
+	"appletvos-arm64",
+	"watchos-arm",
+	"darwin-arm64",
+	"darwin-amd64",
+];
+
+/// Architecture-independent packages can be installed on any client
+pub const ARCHITECTURE_ALL: &str = "all";
+
+/// Returns the client architectures that are able to install a package build
+pub fn compatible_architectures(package_architecture: &str) -> Vec<&str> {
+	match package_architecture {
+		ARCHITECTURE_ALL => CLIENT_ARCHITECTURES.to_vec(),
+		architecture => vec![architecture],
+	}
+}
+
+/// Returns the package architectures that a client is able to install
+pub fn installable_architectures(client_architecture: &str) -> Vec<String> {
+	vec![
+		client_architecture.to_string(),
+		ARCHITECTURE_ALL.to_string(),
+	]
+}
+
+/// Resolves the client's architecture from the `arch` query parameter,
+/// falling back to the `Sec-CH-UA-Arch` client hint when it is absent
+/// Returns an error if an explicitly requested architecture is unknown
+pub fn client_architecture(
+	param: Option<&String>,
+	headers: &HeaderMap,
+) -> Result<Option<String>, String> {
+	if let Some(architecture) = param {
+		return match CLIENT_ARCHITECTURES.contains(&architecture.as_str()) {
+			true => Ok(Some(architecture.to_string())),
+			false => Err(format!(
+				"Query parameter \'arch\' must be one of {}",
+				CLIENT_ARCHITECTURES.join(", ")
+			)),
+		};
+	}
+
+	// Structured client hints quote their values, unknown values are ignored
+	let hint = headers
+		.get("Sec-CH-UA-Arch")
+		.and_then(|header| header.to_str().ok())
+		.map(|header| header.trim().trim_matches('"'));
+
+	match hint {
+		Some(hint) if CLIENT_ARCHITECTURES.contains(&hint) => Ok(Some(hint.to_string())),
+		_ => Ok(None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::http::HeaderValue;
+
+	#[test]
+	fn architecture_param_wins_over_client_hint() {
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			"Sec-CH-UA-Arch",
+			HeaderValue::from_static("\"iphoneos-arm\""),
+		);
+
+		let param = "iphoneos-arm64".to_string();
+		assert_eq!(
+			client_architecture(Some(&param), &headers),
+			Ok(Some("iphoneos-arm64".to_string()))
+		);
+		assert_eq!(
+			client_architecture(None, &headers),
+			Ok(Some("iphoneos-arm".to_string()))
+		);
+	}
+
+	#[test]
+	fn unknown_architectures_are_rejected_or_ignored() {
+		let mut headers = HeaderMap::new();
+		headers.insert("Sec-CH-UA-Arch", HeaderValue::from_static("\"x86\""));
+
+		let param = "x86".to_string();
+		assert!(client_architecture(Some(&param), &headers).is_err());
+		assert_eq!(client_architecture(None, &headers), Ok(None));
+	}
+
+	#[test]
+	fn all_is_compatible_with_every_client() {
+		assert_eq!(
+			compatible_architectures("all").len(),
+			CLIENT_ARCHITECTURES.len()
+		);
+		assert_eq!(
+			compatible_architectures("iphoneos-arm64"),
+			vec!["iphoneos-arm64"]
+		);
+	}
+}
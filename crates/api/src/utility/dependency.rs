@@ -0,0 +1,184 @@
+use crate::types::Package;
+pub use models::{Dependency, PackageRelations, Relation, VersionConstraint};
+
+/// Parses a relationship field such as `Depends` into its relations
+/// Architecture restrictions and build profiles are discarded
+pub fn parse_relations(field: &str) -> Vec<Relation> {
+	field
+		.split(',')
+		.map(|relation| {
+			relation
+				.split('|')
+				.filter_map(parse_dependency)
+				.collect::<Relation>()
+		})
+		.filter(|relation| !relation.is_empty())
+		.collect()
+}
+
+fn parse_dependency(dependency: &str) -> Option<Dependency> {
+	let dependency = dependency.trim();
+	let name_end = dependency
+		.find(|c: char| c.is_whitespace() || matches!(c, '(' | '[' | '<'))
+		.unwrap_or(dependency.len());
+
+	let (name, rest) = dependency.split_at(name_end);
+	if name.is_empty() {
+		return None;
+	}
+
+	let (package, architecture) = match name.split_once(':') {
+		Some((package, architecture)) => (package, Some(architecture.to_string())),
+		None => (name, None),
+	};
+
+	let rest = rest.trim_start();
+	let constraint = match rest.strip_prefix('(') {
+		Some(rest) => rest.split_once(')').and_then(|(constraint, _)| {
+			let constraint = constraint.trim();
+			let operator_end = constraint
+				.find(|c| !matches!(c, '<' | '>' | '='))
+				.unwrap_or(constraint.len());
+
+			let (operator, version) = constraint.split_at(operator_end);
+			let version = version.trim();
+			if operator.is_empty() || version.is_empty() {
+				return None;
+			}
+
+			Some(VersionConstraint {
+				operator: operator.to_string(),
+				version: version.to_string(),
+			})
+		}),
+		None => None,
+	};
+
+	Some(Dependency {
+		package: package.to_string(),
+		architecture,
+		constraint,
+	})
+}
+
+/// Formats a relation back into its control field syntax
+pub fn format_relation(relation: &[Dependency]) -> String {
+	relation
+		.iter()
+		.map(|dependency| dependency.to_string())
+		.collect::<Vec<String>>()
+		.join(" | ")
+}
+
+/// Returns the relations that can be satisfied by the given package
+pub fn relations_on<'a>(relations: &'a [Relation], package_id: &str) -> Vec<&'a Relation> {
+	relations
+		.iter()
+		.filter(|relation| {
+			relation
+				.iter()
+				.any(|dependency| dependency.package == package_id)
+		})
+		.collect()
+}
+
+/// Parses every relationship field of a package
+/// Fields that are missing or empty produce an empty list
+pub fn package_relations(package: &Package) -> PackageRelations {
+	let parse = |field: &Option<String>| match field {
+		Some(value) => parse_relations(value),
+		None => Vec::new(),
+	};
+
+	PackageRelations {
+		depends: parse(&package.depends),
+		pre_depends: parse(&package.pre_depends),
+		conflicts: parse(&package.conflicts),
+		breaks: parse(&package.breaks),
+		provides: parse(&package.provides),
+		replaces: parse(&package.replaces),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn dependency(package: &str, operator: &str, version: &str) -> Dependency {
+		Dependency {
+			package: package.to_string(),
+			architecture: None,
+			constraint: match operator.is_empty() {
+				true => None,
+				false => Some(VersionConstraint {
+					operator: operator.to_string(),
+					version: version.to_string(),
+				}),
+			},
+		}
+	}
+
+	#[test]
+	fn parses_constraints_and_alternatives() {
+		assert_eq!(
+			parse_relations("firmware (>= 14.0), ws.hbang.common (>=1.17) | com.opa334.altlist, mobilesubstrate"),
+			vec![
+				vec![dependency("firmware", ">=", "14.0")],
+				vec![
+					dependency("ws.hbang.common", ">=", "1.17"),
+					dependency("com.opa334.altlist", "", ""),
+				],
+				vec![dependency("mobilesubstrate", "", "")],
+			]
+		);
+	}
+
+	#[test]
+	fn parses_architectures_and_restrictions() {
+		let relations = parse_relations("libc6:any (<< 2.0) [iphoneos-arm] <!nocheck>,, ");
+		assert_eq!(relations.len(), 1);
+		assert_eq!(relations[0][0].package, "libc6");
+		assert_eq!(relations[0][0].architecture.as_deref(), Some("any"));
+		assert_eq!(
+			relations[0][0].constraint,
+			Some(VersionConstraint {
+				operator: "<<".to_string(),
+				version: "2.0".to_string(),
+			})
+		);
+
+		assert!(parse_relations("").is_empty());
+	}
+
+	#[test]
+	fn finds_relations_on_a_package() {
+		let relations =
+			parse_relations("ws.hbang.common.extra, firmware | ws.hbang.common (>= 1.0)");
+		let found = relations_on(&relations, "ws.hbang.common");
+		assert_eq!(found.len(), 1);
+		assert_eq!(found[0][0].package, "firmware");
+		assert!(relations_on(&relations, "ws.hbang").is_empty());
+	}
+
+	#[test]
+	fn checks_version_constraints() {
+		let relation = parse_relations("ws.hbang.common (>= 1.17) | firmware (<< 15.0)");
+		assert!(relation[0][0].satisfied_by("ws.hbang.common", "2.0~beta1", &[]));
+		assert!(!relation[0][0].satisfied_by("ws.hbang.common", "1.16", &[]));
+		assert!(relation[0][1].satisfied_by("firmware", "14.8", &[]));
+		assert!(!relation[0][1].satisfied_by("firmware", "15.0", &[]));
+		assert_eq!(
+			format_relation(&relation[0]),
+			"ws.hbang.common (>= 1.17) | firmware (<< 15.0)"
+		);
+	}
+
+	#[test]
+	fn checks_provided_packages() {
+		let provides = parse_relations("cydia-app, firmware (= 14.5)");
+		let relations = parse_relations("cydia-app, firmware (>= 14.0), firmware (>= 15.0)");
+		assert!(relations[0][0].satisfied_by("com.saurik.cydia", "1.1.36", &provides));
+		assert!(relations[1][0].satisfied_by("com.saurik.cydia", "1.1.36", &provides));
+		assert!(!relations[2][0].satisfied_by("com.saurik.cydia", "1.1.36", &provides));
+	}
+}
@@ -1,3 +1,9 @@
+use axum::http::HeaderMap;
+use std::net::IpAddr;
+
+/// Header reverse proxies append the address they received a request from to
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
 #[derive(Debug)]
 pub struct Brand {
 	pub name: String,
@@ -55,3 +61,111 @@ pub fn parse_user_agent(input: &str) -> Vec<Brand> {
 
 	brands
 }
+
+/// Normalizes a repository URI so that scheme and trailing slashes don't matter
+pub fn normalize_uri(uri: &str) -> String {
+	let uri = uri.trim().to_ascii_lowercase();
+	let uri = uri
+		.strip_prefix("https://")
+		.or_else(|| uri.strip_prefix("http://"))
+		.unwrap_or(&uri);
+
+	uri.trim_end_matches('/').to_string()
+}
+
+/// Filename of a package relative to its repository, without a leading `./`
+pub fn relative_filename(filename: &str) -> &str {
+	filename.trim_start_matches("./").trim_start_matches('/')
+}
+
+/// Builds the download URL of a package from its repository
+/// Flat repositories resolve filenames against their suite directory
+pub fn download_url(uri: &str, suite: &str, component: Option<&str>, filename: &str) -> String {
+	let uri = uri.trim_end_matches('/');
+	let filename = relative_filename(filename);
+
+	match component {
+		Some(_) => format!("{}/{}", uri, filename),
+		None => match suite.trim_start_matches("./").trim_matches('/') {
+			"" => format!("{}/{}", uri, filename),
+			suite => format!("{}/{}/{}", uri, suite, filename),
+		},
+	}
+}
+
+/// Address of the client that sent a request, when it's known
+/// Behind a proxy the last `X-Forwarded-For` entry is the one the proxy saw,
+/// the entries before it are sent by the client and can't be trusted
+pub fn client_ip(
+	headers: &HeaderMap,
+	peer: Option<IpAddr>,
+	trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+	if !trust_forwarded_for {
+		return peer;
+	}
+
+	headers
+		.get_all(FORWARDED_FOR_HEADER)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.last()
+		.and_then(|addr| addr.trim().parse().ok())
+		.or(peer)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize_uri_ignores_scheme_and_trailing_slash() {
+		assert_eq!(normalize_uri("https://repo.chariz.com/"), "repo.chariz.com");
+		assert_eq!(normalize_uri("http://Repo.Chariz.com"), "repo.chariz.com");
+		assert_eq!(
+			normalize_uri("https://apt.procurs.us/"),
+			normalize_uri("apt.procurs.us")
+		);
+	}
+
+	#[test]
+	fn download_urls_follow_repository_layout() {
+		assert_eq!(
+			download_url("https://repo.chariz.com/", "./", None, "./debs/common.deb"),
+			"https://repo.chariz.com/debs/common.deb"
+		);
+		assert_eq!(
+			download_url("https://repo.example.com", "./flat/", None, "debs/a.deb"),
+			"https://repo.example.com/flat/debs/a.deb"
+		);
+		assert_eq!(
+			download_url(
+				"https://apt.procurs.us",
+				"1800",
+				Some("main"),
+				"pool/main/cydia.deb"
+			),
+			"https://apt.procurs.us/pool/main/cydia.deb"
+		);
+	}
+
+	#[test]
+	fn client_ip_only_trusts_the_nearest_proxy() {
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			FORWARDED_FOR_HEADER,
+			"203.0.113.7, 10.0.0.1".parse().unwrap(),
+		);
+		headers.append(FORWARDED_FOR_HEADER, "198.51.100.2".parse().unwrap());
+		let peer = Some(IpAddr::from([10, 0, 0, 2]));
+
+		assert_eq!(client_ip(&headers, peer, false), peer);
+		assert_eq!(
+			client_ip(&headers, peer, true),
+			Some(IpAddr::from([198, 51, 100, 2]))
+		);
+
+		assert_eq!(client_ip(&HeaderMap::new(), None, true), None);
+	}
+}
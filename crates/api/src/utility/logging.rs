@@ -0,0 +1,33 @@
+use crate::utility::RuntimeConfig;
+use anyhow::Result;
+use sentry::integrations::tracing::{layer as sentry_layer, EventFilter};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Installs the subscriber that every log line of the process goes through
+/// Lines logged while handling a request carry the fields of its span,
+/// which includes the request ID
+pub fn init_logging(config: &RuntimeConfig) -> Result<()> {
+	let filter = EnvFilter::try_new(&config.log_level)?;
+
+	// Logs only become breadcrumbs, errors are still reported explicitly
+	let sentry = sentry_layer()
+		.event_filter(|_| EventFilter::Breadcrumb)
+		.span_filter(|_| false);
+
+	let registry = tracing_subscriber::registry().with(filter).with(sentry);
+
+	match config.log_format.as_str() {
+		"pretty" => registry.with(fmt::layer()).try_init()?,
+		_ => registry
+			.with(
+				fmt::layer()
+					.json()
+					.flatten_event(true)
+					.with_current_span(true)
+					.with_span_list(false),
+			)
+			.try_init()?,
+	};
+
+	Ok(())
+}
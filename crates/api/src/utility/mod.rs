@@ -1,9 +1,18 @@
 pub mod api;
+pub mod architecture;
 pub mod config;
+pub mod dependency;
 pub mod http;
+pub mod logging;
 pub mod runtime;
+pub mod signing;
 
 pub use self::api::*;
+pub use self::architecture::*;
 pub use self::config::*;
+pub use self::dependency::*;
 pub use self::http::*;
+pub use self::logging::*;
 pub use self::runtime::*;
+pub use self::signing::*;
+pub use models::compare_versions;
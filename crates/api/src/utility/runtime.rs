@@ -1,14 +1,12 @@
 use anyhow::Error;
 use sentry::integrations::anyhow::capture_anyhow;
+use tracing::error;
 
 /// Takes an error and reports it to Sentry
 pub fn handle_error(err: &Error) {
 	let uuid = capture_anyhow(err);
-	println!("--------------------------");
-	println!("Reporting an error (Sentry UUID: {})", uuid);
-	println!("Error: {}", err);
+	error!(sentry_id = %uuid, "Reporting an error: {}", err);
 	if cfg!(debug_assertions) {
-		println!("{:?}", err);
+		error!("{:?}", err);
 	}
-	println!("--------------------------");
 }
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use openssl::{
+	base64::{decode_block, encode_block},
+	hash::MessageDigest,
+	memcmp,
+	pkey::{PKey, Private},
+	rand::rand_bytes,
+	sign::Signer,
+};
+use tracing::{error, warn};
+
+/// Returns the key opaque tokens are signed with
+/// Without a configured secret a random one is generated, which means tokens
+/// are only accepted by the instance that issued them
+pub fn signing_key(secret: &str) -> Vec<u8> {
+	if !secret.is_empty() {
+		return secret.as_bytes().to_vec();
+	}
+
+	warn!(target: "signing", "CANISTER_CURSOR_SECRET is unset, using a random key");
+	let mut key = vec![0; 32];
+	if let Err(e) = rand_bytes(&mut key) {
+		error!(target: "signing", "Failed to generate a random key: {}", e);
+	}
+
+	key
+}
+
+fn hmac(key: &[u8], payload: &[u8]) -> Result<Vec<u8>> {
+	let key: PKey<Private> = PKey::hmac(key)?;
+	let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+	Ok(signer.sign_oneshot_to_vec(payload)?)
+}
+
+/// Encodes bytes as unpadded URL-safe base64
+fn encode_base64url(data: &[u8]) -> String {
+	encode_block(data)
+		.trim_end_matches('=')
+		.replace('+', "-")
+		.replace('/', "_")
+}
+
+fn decode_base64url(data: &str) -> Option<Vec<u8>> {
+	let mut data = data.replace('-', "+").replace('_', "/");
+	while !data.len().is_multiple_of(4) {
+		data.push('=');
+	}
+
+	decode_block(&data).ok()
+}
+
+/// Compares secrets in constant time, so timing doesn't leak how much matched
+pub fn secrets_match(expected: &[u8], actual: &[u8]) -> bool {
+	expected.len() == actual.len() && memcmp::eq(expected, actual)
+}
+
+/// Wraps a payload into a URL-safe token that can't be altered by clients
+pub fn sign_token(key: &[u8], payload: &[u8]) -> Result<String> {
+	Ok(format!(
+		"{}.{}",
+		encode_base64url(payload),
+		encode_base64url(&hmac(key, payload)?)
+	))
+}
+
+/// Returns the payload of a token if it was signed with the key
+pub fn verify_token(key: &[u8], token: &str) -> Option<Vec<u8>> {
+	let (payload, signature) = token.split_once('.')?;
+	let payload = decode_base64url(payload)?;
+	let signature = decode_base64url(signature)?;
+
+	let expected = hmac(key, &payload).ok()?;
+	match secrets_match(&expected, &signature) {
+		true => Some(payload),
+		false => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tokens_round_trip() {
+		let token = sign_token(b"secret", b"{\"id\":\"filza\"}").unwrap();
+		assert!(!token.contains(['+', '/', '=']));
+		assert_eq!(
+			verify_token(b"secret", &token),
+			Some(b"{\"id\":\"filza\"}".to_vec())
+		);
+	}
+
+	#[test]
+	fn tampered_tokens_are_rejected() {
+		let token = sign_token(b"secret", b"{\"id\":\"filza\"}").unwrap();
+		let (_, signature) = token.split_once('.').unwrap();
+		let forged = format!("{}.{}", encode_base64url(b"{\"id\":\"other\"}"), signature);
+
+		assert_eq!(verify_token(b"secret", &forged), None);
+		assert_eq!(verify_token(b"other", &token), None);
+		assert_eq!(verify_token(b"secret", "garbage"), None);
+	}
+}
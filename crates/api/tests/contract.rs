@@ -0,0 +1,530 @@
+use api::{build_router, helpers::ingest::SinkConfig, utility::RuntimeConfig, AppState};
+use axum::{
+	body::Body,
+	http::{header, Request, StatusCode},
+	routing::get,
+	Json, Router,
+};
+use deadpool_postgres::tokio_postgres::{connect, NoTls};
+use serde_json::{from_slice, from_str, json, Map, Value};
+use std::{env, net::SocketAddr, path::Path};
+use tempfile::tempdir;
+use tower::ServiceExt;
+use url::Url;
+
+/// Schema the fixtures are seeded into, it is recreated on every run
+const TEST_SCHEMA: &str = "canister_contract";
+
+/// Serves the external services that routes call out to
+async fn spawn_stub() -> SocketAddr {
+	let stub = Router::new()
+		.route(
+			"/piracy.json",
+			get(|| async { Json(json!(["mymaliciousrepo.com", "hackyouriphone.org"])) }),
+		)
+		.fallback(|| async { StatusCode::OK });
+
+	let server =
+		axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(stub.into_make_service());
+
+	let addr = server.local_addr();
+	tokio::spawn(server);
+	addr
+}
+
+/// Recreates the test schema and loads the fixtures into it
+async fn seed(database_url: &str) {
+	let (client, connection) = connect(database_url, NoTls)
+		.await
+		.expect("Failed to connect to the test database");
+
+	tokio::spawn(connection);
+	client
+		.batch_execute(&format!(
+			"
+				DROP SCHEMA IF EXISTS {TEST_SCHEMA} CASCADE;
+				CREATE SCHEMA {TEST_SCHEMA};
+				SET search_path TO {TEST_SCHEMA}, public;
+				{}
+			",
+			include_str!("fixtures/seed.sql")
+		))
+		.await
+		.expect("Failed to seed the test database");
+}
+
+/// Runtime config pointing at the fixtures and the stub services
+fn config(database_url: &str, stub: SocketAddr, spool_dir: &Path) -> RuntimeConfig {
+	let separator = match database_url.contains('?') {
+		true => '&',
+		false => '?',
+	};
+
+	RuntimeConfig {
+		meta_name: "Canister".to_string(),
+		meta_code: "cnstr".to_string(),
+		meta_email: "support@canister.me".to_string(),
+		meta_copyright: "Aarnav Tale (c) {year}".to_string(),
+
+		api_endpoint: "https://api.canister.me/v2".to_string(),
+		docs_endpoint: "https://docs.canister.me".to_string(),
+		privacy_endpoint: "https://canister.me/privacy".to_string(),
+		privacy_updated: "2022-03-25".to_string(),
+
+		piracy_url: format!("http://{stub}/piracy.json"),
+		database_url: format!(
+			"{database_url}{separator}options=-csearch_path%3D{TEST_SCHEMA}%2Cpublic"
+		),
+		typesense_url: format!("http://{stub}"),
+		vector_url: format!("http://{stub}/vector"),
+
+		typesense_api_key: "typesense".to_string(),
+		sentry_dsn: String::new(),
+
+		search_engine: "postgres".to_string(),
+		cursor_secret: "contract".to_string(),
+
+		pod_name: "contract".to_string(),
+		ingest_spool_dir: spool_dir.display().to_string(),
+		ingest_sinks: vec![SinkConfig::File(spool_dir.join("events"))],
+		..RuntimeConfig::default()
+	}
+}
+
+/// Looks up a `#/components/schemas/...` reference
+fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+	match schema.get("$ref").and_then(Value::as_str) {
+		Some(reference) => {
+			let name = reference.trim_start_matches("#/components/schemas/");
+			resolve(spec, &spec["components"]["schemas"][name])
+		}
+		None => schema,
+	}
+}
+
+/// Value documented for a parameter, falling back to what its schema allows
+fn parameter_example(parameter: &Value) -> Option<String> {
+	let schema = &parameter["schema"];
+	let example = parameter
+		.get("example")
+		.or_else(|| schema.get("example"))
+		.or_else(|| schema.get("default"))
+		.or_else(|| schema["enum"].get(0))?;
+
+	match example {
+		Value::String(example) => Some(example.clone()),
+		example => Some(example.to_string()),
+	}
+}
+
+/// Builds a value out of the examples documented on a schema
+fn schema_example(spec: &Value, schema: &Value) -> Value {
+	let schema = resolve(spec, schema);
+	if let Some(example) = schema.get("example") {
+		return example.clone();
+	}
+
+	match schema["type"].as_str() {
+		Some("object") => Value::Object(
+			schema["properties"]
+				.as_object()
+				.into_iter()
+				.flatten()
+				.map(|(key, property)| (key.clone(), schema_example(spec, property)))
+				.filter(|(_, example)| !example.is_null())
+				.collect::<Map<String, Value>>(),
+		),
+		Some("array") => json!([schema_example(spec, &schema["items"])]),
+		_ => Value::Null,
+	}
+}
+
+/// Builds a request for an operation out of its documented examples
+/// Only path parameters and required query parameters are sent
+fn build_request(
+	spec: &Value,
+	base_path: &str,
+	path: &str,
+	method: &str,
+	operation: &Value,
+) -> Result<Request<Body>, String> {
+	let mut uri = format!("{base_path}{path}");
+	let mut query = Vec::new();
+
+	for parameter in operation["parameters"].as_array().into_iter().flatten() {
+		let name = parameter["name"].as_str().unwrap_or_default();
+		let location = match parameter["in"].as_str() {
+			Some(location) => location,
+			None => return Err(format!("Parameter '{name}' has no location")),
+		};
+
+		if location == "query" && parameter["required"] != true {
+			continue;
+		}
+
+		let example = match parameter_example(parameter) {
+			Some(example) => example,
+			None => return Err(format!("Parameter '{name}' has no example")),
+		};
+
+		match location {
+			"path" => uri = uri.replace(&format!("{{{name}}}"), &example),
+			"query" => query.push((name.to_string(), example)),
+			_ => {}
+		}
+	}
+
+	if !query.is_empty() {
+		let query = url::form_urlencoded::Serializer::new(String::new())
+			.extend_pairs(query)
+			.finish();
+
+		uri = format!("{uri}?{query}");
+	}
+
+	let request = Request::builder()
+		.method(method.to_uppercase().as_str())
+		.uri(uri);
+
+	let body = operation["requestBody"]["content"]["application/json"]["schema"].clone();
+	let request = match body.is_null() {
+		true => request.body(Body::empty()),
+		false => request
+			.header(header::CONTENT_TYPE, "application/json")
+			.body(Body::from(schema_example(spec, &body).to_string())),
+	};
+
+	request.map_err(|e| format!("Failed to build request ({e})"))
+}
+
+/// Whether a schema documents a property, directly or through `allOf`
+fn documents_property(spec: &Value, schema: &Value, property: &str) -> bool {
+	let schema = resolve(spec, schema);
+	schema["properties"].get(property).is_some()
+		|| schema.get("additionalProperties").is_some()
+		|| schema["allOf"]
+			.as_array()
+			.into_iter()
+			.flatten()
+			.any(|schema| documents_property(spec, schema, property))
+}
+
+/// Validates a value against an OpenAPI 3.0 schema
+/// Properties that aren't documented are reported unless the schema allows
+/// additional properties, since that is how fields drift out of the spec
+fn validate(spec: &Value, schema: &Value, value: &Value, at: &str, errors: &mut Vec<String>) {
+	validate_schema(spec, schema, value, at, true, errors);
+}
+
+/// Members of an `allOf` only document part of an object, so the undocumented
+/// properties are checked against the whole composition instead
+fn validate_schema(
+	spec: &Value,
+	schema: &Value,
+	value: &Value,
+	at: &str,
+	check_undocumented: bool,
+	errors: &mut Vec<String>,
+) {
+	let schema = resolve(spec, schema);
+
+	if value.is_null() {
+		if schema["nullable"] != true {
+			errors.push(format!("{at} is null but not nullable"));
+		}
+
+		return;
+	}
+
+	for member in schema["allOf"].as_array().into_iter().flatten() {
+		validate_schema(spec, member, value, at, false, errors);
+	}
+
+	for keyword in ["oneOf", "anyOf"] {
+		if let Some(options) = schema[keyword].as_array() {
+			let matched = options.iter().any(|option| {
+				let mut option_errors = Vec::new();
+				validate(spec, option, value, at, &mut option_errors);
+				option_errors.is_empty()
+			});
+
+			if !matched {
+				errors.push(format!("{at} matches none of the {keyword} schemas"));
+			}
+		}
+	}
+
+	if let Some(expected) = schema["type"].as_str() {
+		let matches = match expected {
+			"object" => value.is_object(),
+			"array" => value.is_array(),
+			"string" => value.is_string(),
+			"integer" => value.is_i64() || value.is_u64(),
+			"number" => value.is_number(),
+			"boolean" => value.is_boolean(),
+			_ => true,
+		};
+
+		if !matches {
+			errors.push(format!("{at} should be of type {expected}, found {value}"));
+			return;
+		}
+	}
+
+	if let Some(options) = schema["enum"].as_array() {
+		if !options.contains(value) {
+			errors.push(format!("{at} is {value}, which is not in {options:?}"));
+		}
+	}
+
+	if let (Some(minimum), Some(number)) = (schema["minimum"].as_f64(), value.as_f64()) {
+		if number < minimum {
+			errors.push(format!("{at} is {number}, below the minimum of {minimum}"));
+		}
+	}
+
+	if let (Some(maximum), Some(number)) = (schema["maximum"].as_f64(), value.as_f64()) {
+		if number > maximum {
+			errors.push(format!("{at} is {number}, above the maximum of {maximum}"));
+		}
+	}
+
+	if let Some(object) = value.as_object() {
+		for required in schema["required"]
+			.as_array()
+			.into_iter()
+			.flatten()
+			.filter_map(Value::as_str)
+		{
+			if !object.contains_key(required) {
+				errors.push(format!("{at} is missing the required {required}"));
+			}
+		}
+
+		let properties = schema["properties"].as_object();
+		for (key, value) in object {
+			match properties.and_then(|properties| properties.get(key)) {
+				Some(property) => validate(spec, property, value, &format!("{at}.{key}"), errors),
+				None if check_undocumented
+					&& (properties.is_some() || schema.get("allOf").is_some())
+					&& !documents_property(spec, schema, key) =>
+				{
+					errors.push(format!("{at}.{key} is not documented"))
+				}
+				None => {}
+			}
+		}
+	}
+
+	if let (Some(items), Some(schema)) = (value.as_array(), schema.get("items")) {
+		for (index, item) in items.iter().enumerate() {
+			validate(spec, schema, item, &format!("{at}[{index}]"), errors);
+		}
+	}
+}
+
+/// Checks a response against what the operation documents for its status
+async fn check_response(
+	spec: &Value,
+	operation: &Value,
+	response: axum::response::Response,
+) -> Vec<String> {
+	let status = response.status();
+	let content_type = response
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.unwrap_or_default()
+		.to_string();
+
+	let body = match hyper::body::to_bytes(response.into_body()).await {
+		Ok(body) => body,
+		Err(e) => return vec![format!("Failed to read the body ({e})")],
+	};
+
+	let documented = match operation["responses"].get(status.as_str()) {
+		Some(documented) => documented,
+		None => {
+			return vec![format!(
+				"Status {status} is not documented, the body was {}",
+				String::from_utf8_lossy(&body)
+			)]
+		}
+	};
+
+	let content = match documented["content"].as_object() {
+		Some(content) => content,
+		None => return Vec::new(),
+	};
+
+	let schema = match content.get("application/json") {
+		Some(media) => &media["schema"],
+		None => {
+			let media_type = content_type.split(';').next().unwrap_or_default().trim();
+			return match content.contains_key(media_type) || content.contains_key("*/*") {
+				true => Vec::new(),
+				false => vec![format!("Content type {content_type} is not documented")],
+			};
+		}
+	};
+
+	let body = match from_slice::<Value>(&body) {
+		Ok(body) => body,
+		Err(e) => return vec![format!("Body is not JSON ({e})")],
+	};
+
+	let mut errors = Vec::new();
+	validate(spec, schema, &body, "body", &mut errors);
+	errors
+}
+
+/// Requests every `Filename` of a repository's APT index the way APT does,
+/// relative to the repository, and checks that it redirects upstream
+async fn check_apt_filenames(app: &Router, base_path: &str, repository: &str) -> Vec<String> {
+	let archive = format!("{base_path}/jailbreak/repository/{repository}");
+	let index = Request::get(format!(
+		"{archive}/dists/stable/main/binary-iphoneos-arm64/Packages"
+	))
+	.body(Body::empty())
+	.expect("Failed to build request");
+
+	let response = app.clone().oneshot(index).await.expect("Router failed");
+	let body = hyper::body::to_bytes(response.into_body())
+		.await
+		.expect("Failed to read the index");
+
+	let filenames = String::from_utf8_lossy(&body)
+		.lines()
+		.filter_map(|line| line.strip_prefix("Filename: "))
+		.map(str::to_string)
+		.collect::<Vec<String>>();
+
+	if filenames.is_empty() {
+		return vec![format!("The index of {repository} lists no filenames")];
+	}
+
+	let mut failures = Vec::new();
+	for filename in filenames {
+		let request = Request::get(format!("{archive}/{filename}"))
+			.body(Body::empty())
+			.expect("Failed to build request");
+
+		let response = app.clone().oneshot(request).await.expect("Router failed");
+		let location = response
+			.headers()
+			.get(header::LOCATION)
+			.and_then(|location| location.to_str().ok())
+			.unwrap_or_default();
+
+		if response.status() != StatusCode::FOUND || location.is_empty() {
+			failures.push(format!(
+				"{filename} from {repository} returned {} instead of a redirect",
+				response.status()
+			));
+		}
+	}
+
+	failures
+}
+
+/// Calls every documented operation with its examples and checks that the
+/// response matches the spec, against fixtures seeded into Postgres
+/// Set CANISTER_TEST_DATABASE_URL to a database the tests can write to and
+/// run them with `cargo test -- --ignored`
+#[tokio::test]
+#[ignore = "needs a Postgres database in CANISTER_TEST_DATABASE_URL"]
+async fn responses_match_openapi_spec() {
+	let database_url = env::var("CANISTER_TEST_DATABASE_URL")
+		.expect("CANISTER_TEST_DATABASE_URL must point at a database the tests can write to");
+
+	let stub = spawn_stub().await;
+	seed(&database_url).await;
+
+	let spool_dir = tempdir().expect("Failed to create the spool directory");
+	let state = AppState::new(config(&database_url, stub, spool_dir.path()))
+		.await
+		.expect("Failed to build the state");
+
+	let spec: Value = from_str(env!("CANISTER_OPENAPI_JSON")).expect("Failed to parse the spec");
+	let base_path = spec["servers"][0]["url"]
+		.as_str()
+		.and_then(|server| Url::parse(server).ok())
+		.map(|server| server.path().trim_end_matches('/').to_string())
+		.unwrap_or_default();
+
+	let app = build_router(state);
+	let mut failures = Vec::new();
+	let mut checked = 0;
+
+	for (path, operations) in spec["paths"].as_object().into_iter().flatten() {
+		for (method, operation) in operations.as_object().into_iter().flatten() {
+			let label = format!("{} {}", method.to_uppercase(), path);
+			let request = match build_request(&spec, &base_path, path, method, operation) {
+				Ok(request) => request,
+				Err(e) => {
+					failures.push(format!("{label}: {e}"));
+					continue;
+				}
+			};
+
+			let response = match app.clone().oneshot(request).await {
+				Ok(response) => response,
+				Err(e) => {
+					failures.push(format!("{label}: {e}"));
+					continue;
+				}
+			};
+
+			checked += 1;
+			for error in check_response(&spec, operation, response).await {
+				failures.push(format!("{label}: {error}"));
+			}
+		}
+	}
+
+	failures.extend(check_apt_filenames(&app, &base_path, "myrepo").await);
+
+	assert!(checked > 0, "The spec documents no operations");
+	assert!(
+		failures.is_empty(),
+		"Responses drifted from the OpenAPI spec:\n{}",
+		failures.join("\n")
+	);
+}
+
+#[test]
+fn validation_reports_drift() {
+	let spec = json!({
+		"components": {
+			"schemas": {
+				"Error": {
+					"type": "object",
+					"required": ["message"],
+					"properties": {
+						"message": { "type": "string" },
+						"date": { "type": "string" },
+					},
+				},
+			},
+		},
+	});
+
+	let schema = json!({ "$ref": "#/components/schemas/Error" });
+	let mut errors = Vec::new();
+	validate(
+		&spec,
+		&schema,
+		&json!({ "status": "400 Bad Request", "date": null }),
+		"body",
+		&mut errors,
+	);
+
+	assert_eq!(
+		errors,
+		vec![
+			"body is missing the required message",
+			"body.status is not documented",
+			"body.date is null but not nullable",
+		]
+	);
+}
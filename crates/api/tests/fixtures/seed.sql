@@ -0,0 +1,112 @@
+-- Mirrors the tables the indexer maintains, limited to the columns the API reads
+-- Rows line up with the examples documented in crates/openapi/routes
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public;
+
+CREATE TABLE repository (
+	id text PRIMARY KEY,
+	aliases text[],
+	visible boolean NOT NULL DEFAULT true,
+	quality int NOT NULL,
+	package_count bigint NOT NULL DEFAULT 0,
+	sections text[] NOT NULL DEFAULT '{}',
+	bootstrap boolean NOT NULL DEFAULT false,
+	uri text NOT NULL,
+	suite text NOT NULL DEFAULT './',
+	component text,
+	name text,
+	version text,
+	description text,
+	date text,
+	payment_gateway text,
+	sileo_endpoint text,
+	origin_hostname text NOT NULL,
+	origin_release_path text NOT NULL,
+	origin_release_hash text NOT NULL,
+	origin_packages_path text NOT NULL,
+	origin_packages_hash text NOT NULL,
+	origin_last_updated text NOT NULL,
+	origin_has_in_release boolean NOT NULL DEFAULT false,
+	origin_has_release_gpg boolean NOT NULL DEFAULT false,
+	origin_supports_payment_v1 boolean NOT NULL DEFAULT false,
+	origin_supports_payment_v2 boolean NOT NULL DEFAULT false,
+	origin_uses_https boolean NOT NULL DEFAULT true,
+	search_vector tsvector GENERATED ALWAYS AS (
+		to_tsvector('simple', coalesce(name, '') || ' ' || id || ' ' || coalesce(description, ''))
+	) STORED
+);
+
+CREATE TABLE package (
+	id text PRIMARY KEY,
+	package_id text NOT NULL,
+	latest_version boolean NOT NULL,
+	visible boolean NOT NULL DEFAULT true,
+	quality int NOT NULL,
+	repository_id text NOT NULL REFERENCES repository (id),
+	price text NOT NULL DEFAULT 'Free',
+	version text NOT NULL,
+	architecture text NOT NULL,
+	package_filename text NOT NULL,
+	package_size bigint NOT NULL,
+	sha256_hash text,
+	name text,
+	description text,
+	author text,
+	maintainer text,
+	depiction text,
+	native_depiction text,
+	sileo_depiction text,
+	header_url text,
+	tint_color text,
+	icon_url text,
+	section text,
+	tags text[],
+	installed_size bigint,
+	depends text,
+	pre_depends text,
+	conflicts text,
+	breaks text,
+	provides text,
+	replaces text,
+	search_vector tsvector GENERATED ALWAYS AS (
+		to_tsvector('simple', coalesce(name, '') || ' ' || package_id || ' ' || coalesce(description, '') || ' ' || coalesce(author, ''))
+	) STORED
+);
+
+INSERT INTO repository (
+	id, quality, package_count, sections, bootstrap, uri, name, version, description, date,
+	origin_hostname, origin_release_path, origin_release_hash, origin_packages_path,
+	origin_packages_hash, origin_last_updated
+) VALUES
+	('myrepo', 1, 2, '{Tweaks}', false, 'https://repo.example.com', 'MyRepo', '1.0', 'An example repository', '2024-07-23T11:20:05',
+		'repo.example.com', '/Release', 'aa', '/Packages.zst', 'bb', '2024-07-24T03:22:36.216037'),
+	('chariz', 1, 2, '{System,Tweaks}', false, 'https://repo.chariz.com', 'Chariz', '1.0', 'Chariz repository', '2024-07-23T11:20:05',
+		'repo.chariz.com', '/Release', 'aa', '/Packages', 'bb', '2024-07-24T03:22:36.216037'),
+	('havoc', 1, 1, '{Utilities}', false, 'https://havoc.app', 'Havoc', '1.0', 'The Havoc Repository', '2024-07-23T11:20:05',
+		'havoc.app', '/Release', 'aa', '/Packages.zst', 'bb', '2024-07-24T03:22:36.216037'),
+	('procursus', 2, 1, '{System}', true, 'https://apt.procurs.us', 'Procursus', '1.0', 'Bootstrap repository', '2024-07-23T11:20:05',
+		'apt.procurs.us', '/Release', 'aa', '/Packages', 'bb', '2024-07-24T03:22:36.216037');
+
+INSERT INTO package (
+	id, package_id, latest_version, quality, repository_id, price, version, architecture,
+	package_filename, package_size, sha256_hash, name, description, author, section, tags,
+	depends
+) VALUES
+	('c1', 'com.mycompany.mypackage', true, 1, 'myrepo', 'Free', '1.1', 'iphoneos-arm64',
+		'./debs/mypackage_1.1.deb', 1200, 'c1', 'MyPackage', 'An example package', 'My Company', 'Tweaks', '{purpose::extension}',
+		'firmware (>= 14.0), ws.hbang.common (>= 1.17)'),
+	('c2', 'com.mycompany.mypackage', false, 1, 'myrepo', 'Free', '1.0', 'iphoneos-arm',
+		'./debs/mypackage_1.0.deb', 1100, 'c2', 'MyPackage', 'An example package', 'My Company', 'Tweaks', NULL,
+		'ws.hbang.common'),
+	('c3', 'ws.hbang.common', true, 1, 'chariz', 'Free', '2.0', 'iphoneos-arm64',
+		'./debs/common_2.0.deb', 200, 'c3', 'Cephei', 'Support library', 'HASHBANG Productions', 'System', NULL,
+		NULL),
+	('c4', 'com.chariz.paidtweak', true, 1, 'chariz', '$1.99', '1.0', 'iphoneos-arm64',
+		'./debs/paidtweak_1.0.deb', 300, 'c4', 'Paid Tweak', 'A paid tweak', 'Chariz', 'Tweaks', '{cydia::commercial}',
+		'ws.hbang.common (>= 1.17)'),
+	('c5', 'com.tigisoftware.filza', true, 1, 'havoc', 'Free', '4.0.1', 'iphoneos-arm64',
+		'./debs/filza_4.0.1.deb', 1000, 'c5', 'Filza File Manager', 'File manager', 'TIGI Software', 'Utilities', NULL,
+		NULL),
+	('c6', 'ws.hbang.common', true, 2, 'procursus', 'Free', '1.17.1', 'iphoneos-arm',
+		'./pool/common_1.17.1.deb', 210, 'c6', 'Cephei', 'Support library', 'HASHBANG Productions', 'System', NULL,
+		NULL);
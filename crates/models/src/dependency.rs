@@ -0,0 +1,98 @@
+use super::compare_versions;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct VersionConstraint {
+	/// Comparison operator, such as `>=` or `<<`
+	pub operator: String,
+	/// Version the operator compares against
+	pub version: String,
+}
+
+/// A single package named in a relationship field
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Dependency {
+	/// Package identifier
+	pub package: String,
+	/// Architecture qualifier, such as `any` in `perl:any`
+	pub architecture: Option<String>,
+	/// Version the package is restricted to
+	pub constraint: Option<VersionConstraint>,
+}
+
+impl VersionConstraint {
+	/// Checks a version against the constraint, `<` and `>` are the obsolete
+	/// spellings of `<=` and `>=`
+	pub fn satisfied_by(&self, version: &str) -> bool {
+		let ordering = compare_versions(version, &self.version);
+		match self.operator.as_str() {
+			"<<" => ordering == Ordering::Less,
+			"<=" | "<" => ordering != Ordering::Greater,
+			"=" => ordering == Ordering::Equal,
+			">=" | ">" => ordering != Ordering::Less,
+			">>" => ordering == Ordering::Greater,
+			_ => false,
+		}
+	}
+}
+
+impl Dependency {
+	/// Checks whether a package satisfies this dependency by name or by
+	/// providing it, versioned dependencies need a versioned provide
+	pub fn satisfied_by(&self, package_id: &str, version: &str, provides: &[Relation]) -> bool {
+		if package_id == self.package {
+			return match &self.constraint {
+				Some(constraint) => constraint.satisfied_by(version),
+				None => true,
+			};
+		}
+
+		provides.iter().flatten().any(|provided| {
+			provided.package == self.package
+				&& match (&self.constraint, &provided.constraint) {
+					(None, _) => true,
+					(Some(constraint), Some(provided)) => {
+						provided.operator == "=" && constraint.satisfied_by(&provided.version)
+					}
+					(Some(_), None) => false,
+				}
+		})
+	}
+}
+
+impl fmt::Display for Dependency {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.package)?;
+		if let Some(architecture) = &self.architecture {
+			write!(f, ":{}", architecture)?;
+		}
+
+		if let Some(constraint) = &self.constraint {
+			write!(f, " ({} {})", constraint.operator, constraint.version)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// A comma-separated entry, satisfied by any one of its alternatives
+pub type Relation = Vec<Dependency>;
+
+/// Parsed relationship fields of a package
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PackageRelations {
+	/// Parsed Depends relations, each a list of alternatives
+	pub depends: Vec<Relation>,
+	/// Parsed Pre-Depends relations, each a list of alternatives
+	pub pre_depends: Vec<Relation>,
+	/// Parsed Conflicts relations
+	pub conflicts: Vec<Relation>,
+	/// Parsed Breaks relations
+	pub breaks: Vec<Relation>,
+	/// Parsed Provides relations
+	pub provides: Vec<Relation>,
+	/// Parsed Replaces relations
+	pub replaces: Vec<Relation>,
+}
@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Maximum number of values returned for each facet
+pub const MAX_FACET_VALUES: usize = 50;
+
+#[derive(Debug, Serialize, JsonSchema, PartialEq, Eq)]
+pub struct FacetCount {
+	pub value: String,
+	#[validate(range(min = 0))]
+	pub count: i64,
+}
+
+/// Matching package counts per filter value, after every filter is applied
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct Facets {
+	pub section: Vec<FacetCount>,
+	pub architecture: Vec<FacetCount>,
+	pub price: Vec<FacetCount>,
+	pub repository: Vec<FacetCount>,
+	pub author: Vec<FacetCount>,
+	pub tags: Vec<FacetCount>,
+}
+
+impl Facets {
+	/// Adds a count to the named facet, merging it with an existing value
+	pub fn add(&mut self, facet: &str, value: String, count: i64) {
+		let counts = match facet {
+			"section" => &mut self.section,
+			"architecture" => &mut self.architecture,
+			"price" => &mut self.price,
+			"repository" => &mut self.repository,
+			"author" => &mut self.author,
+			"tags" => &mut self.tags,
+			_ => return,
+		};
+
+		match counts.iter_mut().find(|existing| existing.value == value) {
+			Some(existing) => existing.count += count,
+			None => counts.push(FacetCount { value, count }),
+		}
+	}
+
+	/// Orders every facet by descending count and caps its length
+	pub fn finish(mut self) -> Self {
+		for counts in [
+			&mut self.section,
+			&mut self.architecture,
+			&mut self.price,
+			&mut self.repository,
+			&mut self.author,
+			&mut self.tags,
+		] {
+			counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+			counts.truncate(MAX_FACET_VALUES);
+		}
+
+		self
+	}
+}
@@ -0,0 +1,30 @@
+use super::PackageRefs;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Versions of a package hosted by a single repository
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RepositoryVersions {
+	/// The ID of the hosting repository
+	pub repository_id: String,
+	/// The quality of the hosting repository
+	pub quality: i32,
+	/// Versions on the repository, newest first
+	pub versions: Vec<PackageVersion>,
+	pub refs: PackageRefs,
+}
+
+/// A single build of a package on a repository
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PackageVersion {
+	/// Database ID (Unique hash)
+	pub id: String,
+	pub version: String,
+	pub architecture: String,
+	/// Client architectures that can install this build
+	pub compatible_architectures: Vec<String>,
+	pub latest_version: bool,
+	pub package_filename: String,
+	pub package_size: i64,
+	pub sha256_hash: Option<String>,
+}
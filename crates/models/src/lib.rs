@@ -0,0 +1,23 @@
+//! Response models shared by the API and its OpenAPI specification
+//! Handlers serialize these types and the component schemas are derived from
+//! them, so the published spec always matches what is returned
+
+mod dependency;
+mod facets;
+mod history;
+mod package;
+mod repository;
+mod resolve;
+mod responses;
+mod updates;
+mod version;
+
+pub use self::dependency::*;
+pub use self::facets::*;
+pub use self::history::*;
+pub use self::package::*;
+pub use self::repository::*;
+pub use self::resolve::*;
+pub use self::responses::*;
+pub use self::updates::*;
+pub use self::version::*;
@@ -0,0 +1,127 @@
+#![allow(non_snake_case)]
+use super::{PackageRelations, Relation, Repository};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Package {
+	/// Database ID (Unique hash)
+	pub id: String,
+	/// Package identifier
+	pub package_id: String,
+	/// If this is the latest version of the package
+	pub latest_version: bool,
+	/// If this package is visible
+	pub visible: bool,
+	/// The quality of the package's hosting repository
+	pub quality: i32,
+	/// The ID of the package's hosting repository
+	pub repository_id: String,
+	/// Price of the package
+	pub price: String,
+	/// Package version
+	pub version: String,
+	/// Supported architecture
+	pub architecture: String,
+	/// Filename of the package on the repository
+	pub package_filename: String,
+	/// Download size of the package in bytes
+	pub package_size: i64,
+	/// SHA256 hash of the package
+	pub sha256_hash: Option<String>,
+	/// Package name
+	pub name: Option<String>,
+	/// Package description
+	pub description: Option<String>,
+	/// Package author
+	pub author: Option<String>,
+	/// Package maintainer
+	pub maintainer: Option<String>,
+	/// URL to the package's web depiction
+	pub depiction: Option<String>,
+	/// URL to the package's native depiction V2
+	pub native_depiction: Option<String>,
+	/// URL to the package's Sileo depiction
+	pub sileo_depiction: Option<String>,
+	/// URL to the package's header image
+	pub header_url: Option<String>,
+	/// Tint color of the package (from Sileo)
+	pub tint_color: Option<String>,
+	/// URL to the package's icon
+	pub icon_url: Option<String>,
+	/// Package section
+	pub section: Option<String>,
+	/// Package tags
+	pub tags: Option<Vec<String>>,
+	/// Size of the package when installed in bytes
+	pub installed_size: Option<i64>,
+	/// Raw Depends control field
+	pub depends: Option<String>,
+	/// Raw Pre-Depends control field
+	pub pre_depends: Option<String>,
+	/// Raw Conflicts control field
+	pub conflicts: Option<String>,
+	/// Raw Breaks control field
+	pub breaks: Option<String>,
+	/// Raw Provides control field
+	pub provides: Option<String>,
+	/// Raw Replaces control field
+	pub replaces: Option<String>,
+
+	/// Client architectures that are able to install this build
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub compatible_architectures: Option<Vec<String>>,
+	/// Parsed relationship fields
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub relations: Option<PackageRelations>,
+	/// Links to related endpoints
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub refs: Option<PackageRefs>,
+}
+
+/// A package search result, which keeps the fields search has always returned
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchPackage {
+	#[serde(flatten)]
+	pub base: Package,
+
+	// Old fields that are grandfathered in
+	/// Package identifier (legacy)
+	#[deprecated]
+	pub package: String,
+	/// Package repository tier (legacy)
+	#[deprecated]
+	pub repositoryTier: i32,
+	/// Package depiction for Sileo (legacy)
+	#[deprecated]
+	pub sileoDepiction: Option<String>,
+	/// Hosting repository
+	pub repository: Repository,
+}
+
+/// A package that depends on the one that was looked up
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Dependent {
+	#[serde(flatten)]
+	pub base: Package,
+	/// Relations of the dependent that name the package
+	pub dependency: DependentRelations,
+}
+
+/// Relations that name a package, fields without any are left out
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DependentRelations {
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub depends: Vec<Relation>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub pre_depends: Vec<Relation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PackageRefs {
+	/// URL to the package metadata
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub meta: Option<String>,
+	/// URL to the repository metadata
+	pub repo: String,
+}
@@ -0,0 +1,86 @@
+#![allow(non_snake_case)]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Repository {
+	/// Repository identifier
+	pub id: String,
+	/// Repository aliases
+	pub aliases: Option<Vec<String>>,
+	/// If the repository is visible
+	pub visible: bool,
+	/// Quality of the repository
+	pub quality: i32,
+	/// Number of packages in the repository
+	pub package_count: i64,
+	/// Repository sections
+	pub sections: Vec<String>,
+	/// If the repository is a bootstrap repository
+	pub bootstrap: bool,
+	/// URL to the repository
+	pub uri: String,
+	/// Debian APT suite
+	pub suite: String,
+	/// Debian APT component
+	pub component: Option<String>,
+	/// Repository name
+	pub name: Option<String>,
+	/// Repository version
+	pub version: Option<String>,
+	/// Repository description
+	pub description: Option<String>,
+	/// Repository last updated date
+	pub date: Option<String>,
+	/// Repository payment gateway V2
+	pub payment_gateway: Option<String>,
+	/// Repository Sileo endpoint
+	pub sileo_endpoint: Option<String>,
+
+	/// Hostname of the repository origin
+	pub origin_hostname: String,
+	/// Path to the repository Release file
+	pub origin_release_path: String,
+	/// Hash of the repository Release file
+	pub origin_release_hash: String,
+	/// Path to the repository Packages file
+	pub origin_packages_path: String,
+	/// Hash of the repository Packages file
+	pub origin_packages_hash: String,
+	/// Last updated date of the repository origin
+	pub origin_last_updated: String,
+	/// Repository has an InRelease file
+	pub origin_has_in_release: bool,
+	/// Repository has a Release.gpg file
+	pub origin_has_release_gpg: bool,
+	/// Repository supports payment V1 (Sileo)
+	pub origin_supports_payment_v1: bool,
+	/// Repository supports payment V2 (Native)
+	pub origin_supports_payment_v2: bool,
+	/// Repository uses HTTPS
+	pub origin_uses_https: bool,
+
+	// Old fields that are grandfathered in
+	/// Repository slug (legacy)
+	#[deprecated]
+	pub slug: String,
+	/// Repository tier (legacy)
+	#[deprecated]
+	pub tier: i32,
+	/// If the repository is a bootstrap repository (legacy)
+	#[deprecated]
+	pub isBootstrap: bool,
+
+	/// Links to related endpoints, not present on embedded repositories
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub refs: Option<RepositoryRefs>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RepositoryRefs {
+	/// URL to the repository metadata
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub meta: Option<String>,
+	/// URL to the list of packages in the repository
+	pub packages: String,
+}
@@ -0,0 +1,47 @@
+use super::PackageRefs;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Packages that have to be installed to satisfy a request
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct InstallPlan {
+	pub count: usize,
+	/// Combined download size of the plan in bytes
+	pub total_size: i64,
+	/// Packages to install, requested packages come before their dependencies
+	pub packages: Vec<PlannedPackage>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlannedPackage {
+	/// Database ID (Unique hash)
+	pub id: String,
+	pub package_id: String,
+	pub version: String,
+	pub architecture: String,
+	pub repository_id: String,
+	pub package_filename: String,
+	pub package_size: i64,
+	pub sha256_hash: Option<String>,
+	/// Where the package is downloaded from on its repository
+	pub download_url: String,
+	pub refs: PackageRefs,
+}
+
+/// Why a request could not be resolved into a plan
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ResolveFailure {
+	pub message: String,
+	pub unsatisfiable: Vec<Unsatisfiable>,
+}
+
+/// A relation that could not be satisfied and why
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Unsatisfiable {
+	/// The package declaring the relation, null for requested packages
+	pub required_by: Option<String>,
+	/// The relation as written in the control field
+	pub relation: String,
+	/// Why each alternative could not be used
+	pub reasons: Vec<String>,
+}
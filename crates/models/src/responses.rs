@@ -0,0 +1,58 @@
+#![allow(non_snake_case)]
+use super::Facets;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// Envelope of a response holding a single object
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DataResponse<T> {
+	/// HTTP status code and reason of the response
+	pub status: String,
+	/// Date and time the response was generated
+	pub date: String,
+	pub data: T,
+}
+
+/// Envelope of a response holding a list of objects
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListResponse<T> {
+	/// HTTP status code and reason of the response
+	pub status: String,
+	/// Date and time the response was generated
+	pub date: String,
+	/// Links to the surrounding pages of a paginated listing
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub refs: Option<PageRefs>,
+	/// Matching package counts per filter value, after every filter is applied
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub facets: Option<Facets>,
+	/// Number of results in this response
+	pub count: usize,
+	/// Number of results matching the query across every page
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub total: Option<i64>,
+	/// Whether another page follows this one
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub hasMore: Option<bool>,
+	pub data: T,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct PageRefs {
+	/// Link to the following page
+	pub nextPage: Option<String>,
+	/// Link to the preceding page
+	pub previousPage: Option<String>,
+}
+
+/// Envelope of a response for a request that failed
+// A few endpoints replace the message with an object describing the failure
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorResponse<E = String> {
+	/// HTTP status code and reason of the response
+	pub status: String,
+	/// Date and time the response was generated
+	pub date: String,
+	/// Description of what went wrong
+	pub error: E,
+}
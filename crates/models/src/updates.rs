@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// A newer version of an installed package
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PackageUpdate {
+	/// Package identifier
+	pub package_id: String,
+	/// Version the client has installed
+	pub installed_version: String,
+	/// The newer version that is available
+	pub version: String,
+	/// Database ID (Unique hash) of the newer version
+	pub id: String,
+	pub architecture: String,
+	pub package_filename: String,
+	pub repository_id: String,
+	pub repository_uri: String,
+	/// Whether the update comes from a different repository than the installed package
+	pub repository_changed: bool,
+	/// Size and hash of the installed version, these are null if it is not indexed
+	pub changes: UpdateChanges,
+	pub refs: UpdateRefs,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UpdateChanges {
+	pub package_size: SizeChange,
+	pub sha256_hash: HashChange,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SizeChange {
+	pub from: Option<i64>,
+	pub to: i64,
+	pub delta: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HashChange {
+	pub from: Option<String>,
+	pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UpdateRefs {
+	/// URL to the package metadata
+	pub package: String,
+	/// URL to the repository metadata
+	pub repo: String,
+}
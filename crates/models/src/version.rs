@@ -0,0 +1,275 @@
+use std::cmp::Ordering;
+
+/// A Debian package version split into `[epoch:]upstream[-revision]`
+/// Ordering follows the rules used by `dpkg --compare-versions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebianVersion<'a> {
+	pub epoch: u64,
+	pub upstream: &'a str,
+	pub revision: &'a str,
+}
+
+impl<'a> DebianVersion<'a> {
+	/// Splits a version string into its components
+	/// Malformed epochs are treated as part of the upstream version
+	pub fn parse(version: &'a str) -> Self {
+		let version = version.trim();
+
+		let (epoch, rest) = match version.split_once(':') {
+			Some((epoch, rest)) => match epoch.parse::<u64>() {
+				Ok(epoch) => (epoch, rest),
+				Err(_) => (0, version),
+			},
+			None => (0, version),
+		};
+
+		let (upstream, revision) = match rest.rsplit_once('-') {
+			Some((upstream, revision)) => (upstream, revision),
+			None => (rest, ""),
+		};
+
+		DebianVersion {
+			epoch,
+			upstream,
+			revision,
+		}
+	}
+}
+
+impl Ord for DebianVersion<'_> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.epoch
+			.cmp(&other.epoch)
+			.then_with(|| compare_fragment(self.upstream, other.upstream))
+			.then_with(|| compare_fragment(self.revision, other.revision))
+	}
+}
+
+impl PartialOrd for DebianVersion<'_> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Compares two version strings the same way `dpkg` does
+pub fn compare_versions(left: &str, right: &str) -> Ordering {
+	DebianVersion::parse(left).cmp(&DebianVersion::parse(right))
+}
+
+/// Sort weight of a non-digit character, `None` is the end of the string
+/// Tildes sort before everything, even the end, and letters before symbols
+fn order(c: Option<u8>) -> i32 {
+	match c {
+		None => 0,
+		Some(c) if c.is_ascii_digit() => 0,
+		Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+		Some(b'~') => -1,
+		Some(c) => i32::from(c) + 256,
+	}
+}
+
+/// Compares an upstream version or revision with `dpkg`'s `verrevcmp`
+/// Alternates between comparing non-digit runs and numeric runs
+fn compare_fragment(left: &str, right: &str) -> Ordering {
+	let left = left.as_bytes();
+	let right = right.as_bytes();
+	let (mut i, mut j) = (0, 0);
+
+	while i < left.len() || j < right.len() {
+		while (i < left.len() && !left[i].is_ascii_digit())
+			|| (j < right.len() && !right[j].is_ascii_digit())
+		{
+			let l = order(left.get(i).copied());
+			let r = order(right.get(j).copied());
+			if l != r {
+				return l.cmp(&r);
+			}
+
+			i += 1;
+			j += 1;
+		}
+
+		while i < left.len() && left[i] == b'0' {
+			i += 1;
+		}
+
+		while j < right.len() && right[j] == b'0' {
+			j += 1;
+		}
+
+		// The first differing digit decides unless one number is longer
+		let mut first_diff = Ordering::Equal;
+		while i < left.len()
+			&& j < right.len()
+			&& left[i].is_ascii_digit()
+			&& right[j].is_ascii_digit()
+		{
+			if first_diff == Ordering::Equal {
+				first_diff = left[i].cmp(&right[j]);
+			}
+
+			i += 1;
+			j += 1;
+		}
+
+		if i < left.len() && left[i].is_ascii_digit() {
+			return Ordering::Greater;
+		}
+
+		if j < right.len() && right[j].is_ascii_digit() {
+			return Ordering::Less;
+		}
+
+		if first_diff != Ordering::Equal {
+			return first_diff;
+		}
+	}
+
+	Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cmp::Ordering::{Equal, Greater, Less};
+
+	fn assert_order(left: &str, right: &str, expected: Ordering) {
+		assert_eq!(
+			compare_versions(left, right),
+			expected,
+			"{} vs {}",
+			left,
+			right
+		);
+
+		assert_eq!(
+			compare_versions(right, left),
+			expected.reverse(),
+			"{} vs {}",
+			right,
+			left
+		);
+	}
+
+	#[test]
+	fn parses_components() {
+		assert_eq!(
+			DebianVersion::parse("1:2.0-82b9fb9"),
+			DebianVersion {
+				epoch: 1,
+				upstream: "2.0",
+				revision: "82b9fb9",
+			}
+		);
+
+		assert_eq!(
+			DebianVersion::parse("9:1.18.36:5.4-20"),
+			DebianVersion {
+				epoch: 9,
+				upstream: "1.18.36:5.4",
+				revision: "20",
+			}
+		);
+
+		assert_eq!(
+			DebianVersion::parse("1.0-pre2-b6-2"),
+			DebianVersion {
+				epoch: 0,
+				upstream: "1.0-pre2-b6",
+				revision: "2",
+			}
+		);
+	}
+
+	#[test]
+	fn equal_versions() {
+		assert_order("1.0", "1.0", Equal);
+		assert_order("0:1.0", "1.0", Equal);
+		assert_order("1.0", "1.00", Equal);
+		assert_order("1.0-0", "1.0", Equal);
+		assert_order("0-pre", "0-pre", Equal);
+		assert_order("0:1.18.36", "1.18.36", Equal);
+	}
+
+	#[test]
+	fn numeric_components() {
+		assert_order("1.0", "1.1", Less);
+		assert_order("1.9", "1.10", Less);
+		assert_order("1.2.3", "1.2.3.0", Less);
+		assert_order("1.18.35", "1.18.36", Less);
+		assert_order("1.0-1", "1.0-2", Less);
+		assert_order("1-1", "1-1.1", Less);
+		assert_order("1:1.25-4", "1:1.25-8", Less);
+	}
+
+	#[test]
+	fn epochs() {
+		assert_order("1:0.1", "2.0", Greater);
+		assert_order("2:1", "1:2", Greater);
+		assert_order("10.3", "1:0.4", Less);
+		assert_order("1:9.8", "4.0.1", Greater);
+		assert_order("9:1.18.36:5.4-20", "10:0.5.1-22", Less);
+		assert_order("9:1.18.36:5.4-20", "9:1.18.37:4.3-22", Less);
+	}
+
+	#[test]
+	fn tilde_sorts_before_everything() {
+		assert_order("1.0~beta1", "1.0", Less);
+		assert_order("1.0~rc1", "1.0~rc2", Less);
+		assert_order("1.0~~", "1.0~~a", Less);
+		assert_order("1.0~~a", "1.0~", Less);
+		assert_order("1.0~", "1.0", Less);
+		assert_order("1.0-1~bpo1", "1.0-1", Less);
+		assert_order("2.0~beta1", "2.0-82b9fb9", Less);
+	}
+
+	#[test]
+	fn letters_sort_before_symbols() {
+		assert_order("1.0", "1.0a", Less);
+		assert_order("1a", "1+b", Less);
+		assert_order("1.0", "1.0+dfsg", Less);
+		assert_order("a", "b", Less);
+		assert_order("0-pre", "0-pree", Less);
+	}
+
+	#[test]
+	fn mixed_upstream_versions() {
+		assert_order("7.6p2-4", "7.6-0", Greater);
+		assert_order("1.0.3-3", "1.0-1", Greater);
+		assert_order("1.3", "1.2.2-2", Greater);
+		assert_order("1.3", "1.2.2", Greater);
+		assert_order("1.1.6r2-2", "1.1.6r-1", Greater);
+		assert_order("2.6b2-1", "2.6b-2", Greater);
+		assert_order("98.1p5-1", "98.1-pre2-b6-2", Less);
+		assert_order("0.4a6-2", "0.4-1", Greater);
+		assert_order("1:3.0.5-2", "1:3.0.5.1", Less);
+		assert_order("1.18.36-0.17.35-18", "1.18.37-1", Less);
+	}
+
+	#[test]
+	fn sorting_a_version_history() {
+		let mut versions = vec![
+			"2.0-82b9fb9",
+			"1.0",
+			"2.0~beta1",
+			"1:0.1",
+			"2.0",
+			"1.10",
+			"1.9",
+		];
+
+		versions.sort_by(|a, b| compare_versions(a, b));
+		assert_eq!(
+			versions,
+			vec![
+				"1.0",
+				"1.9",
+				"1.10",
+				"2.0~beta1",
+				"2.0",
+				"2.0-82b9fb9",
+				"1:0.1"
+			]
+		);
+	}
+}
@@ -0,0 +1,135 @@
+use models::{
+	DataResponse, Dependent, ErrorResponse, InstallPlan, ListResponse, Package, PackageUpdate,
+	Repository, RepositoryVersions, ResolveFailure, SearchPackage,
+};
+use schemars::{
+	gen::{SchemaGenerator, SchemaSettings},
+	JsonSchema,
+};
+use serde_json::{to_value, Map, Value};
+
+/// Generates component schemas from the models the API serializes
+/// Models nested inside of them are emitted as their own components
+pub fn generate_components() -> Map<String, Value> {
+	let mut generator = SchemaSettings::openapi3().into_generator();
+	let mut components = Map::new();
+
+	add_component::<ErrorResponse>(&mut generator, &mut components, "Error");
+	add_component::<DataResponse<Repository>>(
+		&mut generator,
+		&mut components,
+		"RepositoryResponse",
+	);
+	add_component::<ListResponse<Vec<Package>>>(&mut generator, &mut components, "PackageList");
+	add_component::<ListResponse<Vec<SearchPackage>>>(
+		&mut generator,
+		&mut components,
+		"SearchPackageList",
+	);
+	add_component::<ListResponse<Vec<Repository>>>(
+		&mut generator,
+		&mut components,
+		"RepositoryList",
+	);
+	add_component::<ListResponse<Vec<PackageUpdate>>>(
+		&mut generator,
+		&mut components,
+		"PackageUpdateList",
+	);
+	add_component::<DataResponse<InstallPlan>>(
+		&mut generator,
+		&mut components,
+		"InstallPlanResponse",
+	);
+	add_component::<ErrorResponse<ResolveFailure>>(&mut generator, &mut components, "ResolveError");
+	add_component::<ListResponse<Vec<Dependent>>>(&mut generator, &mut components, "DependentList");
+	add_component::<ListResponse<Vec<RepositoryVersions>>>(
+		&mut generator,
+		&mut components,
+		"VersionHistory",
+	);
+
+	components
+}
+
+/// Adds the schema of a model under the given name, along with every model
+/// it references
+fn add_component<T: JsonSchema>(
+	generator: &mut SchemaGenerator,
+	components: &mut Map<String, Value>,
+	name: &str,
+) {
+	let root = generator.root_schema_for::<T>();
+
+	for (definition, schema) in root.definitions {
+		match to_value(schema) {
+			Ok(schema) => components.insert(definition, schema),
+			Err(err) => panic!("Failed to serialize schema {} ({})", definition, err),
+		};
+	}
+
+	// Generic models are titled after their type parameters, like
+	// 'ListResponse_for_Array_of_Package', which is noise in the spec
+	let mut schema = match to_value(root.schema) {
+		Ok(schema) => schema,
+		Err(err) => panic!("Failed to serialize schema {} ({})", name, err),
+	};
+
+	if let Some(schema) = schema.as_object_mut() {
+		schema.remove("title");
+	}
+
+	components.insert(name.to_string(), schema);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+		match value {
+			Value::Object(object) => {
+				for (key, value) in object {
+					match (key.as_str(), value) {
+						("$ref", Value::String(reference)) => refs.push(reference.clone()),
+						_ => collect_refs(value, refs),
+					}
+				}
+			}
+			Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+			_ => {}
+		}
+	}
+
+	#[test]
+	fn component_refs_resolve() {
+		let components = generate_components();
+		let mut refs = Vec::new();
+		collect_refs(&Value::Object(components.clone()), &mut refs);
+
+		assert!(!refs.is_empty());
+		for reference in refs {
+			let name = reference.trim_start_matches("#/components/schemas/");
+			assert!(components.contains_key(name), "Unresolved {}", reference);
+		}
+	}
+
+	#[test]
+	fn package_schema_matches_model() {
+		let components = generate_components();
+		let properties = &components["Package"]["properties"];
+
+		assert_eq!(properties["tags"]["items"]["type"], "string");
+		assert_eq!(properties["package_size"]["format"], "int64");
+		assert!(properties.get("package").is_none());
+		assert_eq!(
+			properties["refs"]["allOf"][0]["$ref"],
+			"#/components/schemas/PackageRefs"
+		);
+
+		// Only search keeps the legacy fields
+		let properties = &components["SearchPackage"]["properties"];
+		assert_eq!(properties["package_id"]["type"], "string");
+		assert_eq!(properties["package"]["deprecated"], true);
+	}
+}
@@ -1,13 +1,15 @@
 use chrono::{Datelike, Utc};
+use components::generate_components;
 use schema::{generate_schema, Schema};
 use serde::Serialize;
-use serde_json::{Map, Value};
+use serde_json::Value;
 use serde_yaml::from_str;
 use std::{
 	fs::{read_dir, read_to_string},
 	path::Path,
 };
 
+mod components;
 mod schema;
 
 #[warn(clippy::all)]
@@ -17,7 +19,6 @@ mod schema;
 #[warn(clippy::style)]
 #[warn(clippy::complexity)]
 #[warn(clippy::perf)]
-
 /// Metadatata needed to generate `OpenAPI`
 pub struct Metadata {
 	pub name: String,
@@ -106,6 +107,7 @@ pub fn generate_openapi(meta: &Metadata) -> OpenAPI {
 }
 
 /// Reads schemas, populates descriptions, and returns them as a Value
+/// Schemas of the response models are generated from their Rust types
 /// This is used to populate the components section of the OpenAPI schema
 fn generate_schemas(cwd: &str) -> Value {
 	let joined_cwd = format!("{}/schemas", cwd);
@@ -147,8 +149,8 @@ fn generate_schemas(cwd: &str) -> Value {
 		})
 		.collect::<Vec<Value>>();
 
-	return Value::Object({
-		let mut map = Map::new();
+	Value::Object({
+		let mut map = generate_components();
 		for schema in schemas {
 			let schema = match schema.as_object() {
 				Some(schema) => schema,
@@ -162,7 +164,7 @@ fn generate_schemas(cwd: &str) -> Value {
 			}
 		}
 		map
-	});
+	})
 }
 
 /// Reads routes, populates descriptions, and returns them as a Value
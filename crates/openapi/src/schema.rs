@@ -16,15 +16,8 @@ pub struct Schema {
 /// Generates the OpenAPI compliant schema from the provided options
 /// Recursively transverses the object to handle nested objects and arrays
 pub fn generate_schema(mut options: Schema) -> Value {
-	let nullables = match options.nullables {
-		Some(options) => options,
-		None => Vec::<String>::new(),
-	};
-
-	let deprecated = match options.deprecated {
-		Some(options) => options,
-		None => Vec::<String>::new(),
-	};
+	let nullables = options.nullables.unwrap_or_default();
+	let deprecated = options.deprecated.unwrap_or_default();
 
 	let schema = translate_schema(
 		&mut options.schema,
@@ -57,9 +50,7 @@ fn translate_schema(
 					},
 					"nullable": nullables.contains(key),
 					"deprecated": deprecated.contains(key),
-					"items": {
-						"type": "string"
-					}
+					"items": translate_items(value),
 				}),
 			);
 
@@ -118,6 +109,44 @@ fn translate_schema(
 	})
 }
 
+/// Translates the items of an example array, defaulting to strings
+/// Nested arrays and objects are described by their first element
+fn translate_items(value: &Value) -> Value {
+	match value.as_array().and_then(|items| items.first()) {
+		Some(Value::Array(_)) => json!({
+			"type": "array",
+			"items": translate_items(&value[0]),
+		}),
+		Some(Value::Object(object)) => {
+			let properties = object
+				.iter()
+				.map(|(key, value)| {
+					let property = match value {
+						Value::Array(_) => json!({
+							"type": "array",
+							"items": translate_items(value),
+						}),
+						Value::Object(_) => translate_items(&json!([value])),
+						Value::Null => json!({ "nullable": true }),
+						value => json!({
+							"type": get_type(&mut value.clone()),
+							"example": value,
+						}),
+					};
+
+					(key.to_string(), property)
+				})
+				.collect::<Map<String, Value>>();
+
+			json!({
+				"type": "object",
+				"properties": properties,
+			})
+		}
+		_ => json!({ "type": "string" }),
+	}
+}
+
 /// Returns the OpenAPI type for the provided value
 fn get_type(value: &mut Value) -> &'static str {
 	if value.is_array() {